@@ -0,0 +1,174 @@
+use std::ops::{Range, RangeInclusive};
+
+use crate::piece::{mask_cells, Piece, PieceColor};
+
+/// Number of columns on a standard board.
+pub const BOARD_WIDTH: usize = 10;
+/// Number of rows on a standard board.
+pub const BOARD_HEIGHT: usize = 20;
+/// Narrowest and widest boards supported. Every piece fits across four columns.
+pub const BOARD_WIDTH_RANGE: RangeInclusive<usize> = 4..=40;
+/// Shortest and tallest boards supported, counting only the visible rows.
+pub const BOARD_HEIGHT_RANGE: RangeInclusive<usize> = 4..=80;
+/// Fewest and most hidden rows supported above the visible rows.
+pub const HIDDEN_ROWS_RANGE: RangeInclusive<usize> = 0..=40;
+
+/// Board where pieces are placed/represented.
+///
+/// Rows are counted from the top of a buffer of hidden rows above the visible playfield,
+/// so the visible rows are `hidden_rows()..total_height()`. Pieces can move, rotate and
+/// lock in the hidden rows, but frontends only draw the visible ones.
+#[derive(Clone, Debug)]
+pub struct Board {
+    /// Number of columns.
+    width: usize,
+    /// Number of visible rows.
+    height: usize,
+    /// Number of hidden rows above the visible ones.
+    hidden_rows: usize,
+    /// Cells of the board, row by row from the top of the hidden rows.
+    cells: Vec<Option<PieceColor>>
+}
+
+impl Board {
+    /// Constructor for an empty board of the given size with `hidden_rows` more rows above it,
+    /// each clamped to the supported ranges.
+    pub fn new(width: usize, height: usize, hidden_rows: usize) -> Self {
+        let width = width.clamp(*BOARD_WIDTH_RANGE.start(), *BOARD_WIDTH_RANGE.end());
+        let height = height.clamp(*BOARD_HEIGHT_RANGE.start(), *BOARD_HEIGHT_RANGE.end());
+        let hidden_rows = hidden_rows.clamp(*HIDDEN_ROWS_RANGE.start(), *HIDDEN_ROWS_RANGE.end());
+        Board { width, height, hidden_rows, cells: vec![None; width * (hidden_rows + height)] }
+    }
+
+    /// Number of columns.
+    pub fn width(&self) -> usize { self.width }
+
+    /// Number of visible rows.
+    pub fn height(&self) -> usize { self.height }
+
+    /// Number of hidden rows above the visible ones.
+    pub fn hidden_rows(&self) -> usize { self.hidden_rows }
+
+    /// Number of rows, hidden and visible.
+    pub fn total_height(&self) -> usize { self.hidden_rows + self.height }
+
+    /// Rows shown to the player, from the top.
+    pub fn visible_rows(&self) -> Range<usize> { self.hidden_rows..self.total_height() }
+
+    /// Is the given row above the visible playfield.
+    pub fn is_hidden(&self, y: i8) -> bool {
+        y < self.hidden_rows as i8
+    }
+
+    /// Column a piece with the given rotation mask enters the board at, so that its leftmost
+    /// block lands on `column` of a standard board. Wider and narrower boards move the piece
+    /// by half the difference, keeping it inside the walls.
+    pub fn spawn_x(&self, mask: u32, column: i8) -> i8 {
+        let left = mask_cells(mask).map(|(dx, _)| dx).min().unwrap_or(0);
+        let right = mask_cells(mask).map(|(dx, _)| dx).max().unwrap_or(0);
+        let column = column + (self.width as i8 - BOARD_WIDTH as i8) / 2;
+        column.clamp(0, self.width as i8 - 1 - (right - left)) - left
+    }
+
+    /// Row a piece with the given rotation mask enters the board at, so that its lowest
+    /// blocks sit on the top visible row and the rest of it is hidden. Without enough
+    /// hidden rows pieces enter with their 4x4 box at the very top instead, which leaves
+    /// them room to rotate.
+    pub fn spawn_y(&self, mask: u32) -> i8 {
+        let bottom = mask_cells(mask).map(|(_, dy)| dy).max().unwrap_or(0);
+        (self.hidden_rows as i8 - bottom).max(0)
+    }
+
+    /// Color of the block at the given cell, or `None` if the cell is empty.
+    pub fn get(&self, x: usize, y: usize) -> Option<PieceColor> {
+        self.cells[y * self.width + x]
+    }
+
+    /// Fill or empty the given cell.
+    pub fn set(&mut self, x: usize, y: usize, cell: Option<PieceColor>) {
+        self.cells[y * self.width + x] = cell;
+    }
+
+    /// Does the board have no blocks left on it.
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|cell| cell.is_none())
+    }
+
+    /// Is the given cell outside of the board or already taken by a block.
+    pub fn is_solid(&self, x: i8, y: i8) -> bool {
+        if (x < 0) || (x as usize >= self.width) || (y < 0) || (y as usize >= self.total_height()) {
+            return true;
+        }
+        self.get(x as usize, y as usize).is_some()
+    }
+
+    /// Checks if a rotation mask placed at the given x/y value overlaps a wall or another block.
+    pub fn collides(&self, mask: u32, x: i8, y: i8) -> bool {
+        mask_cells(mask).any(|(dx, dy)| self.is_solid(x + dx, y + dy))
+    }
+
+    /// Set the positions on the board taken by the given piece to its color.
+    pub fn commit(&mut self, piece: &Piece) {
+        for (x, y) in piece.blocks() {
+            self.set(x as usize, y as usize, Some(piece.piece_color));
+        }
+    }
+
+    /// Every complete line, from the top.
+    pub fn full_lines(&self) -> Vec<usize> {
+        (0..self.total_height()).filter(|&y| self.row(y).iter().all(|cell| cell.is_some())).collect()
+    }
+
+    /// Remove every complete line and return how many were removed.
+    pub fn remove_lines(&mut self) -> i16 {
+        let mut n = 0;
+        let mut y = self.total_height();
+
+        while y > 0 {
+            if self.row(y - 1).iter().all(|cell| cell.is_some()) {
+                self.remove_line(y - 1);
+                n += 1;
+            }
+            else {
+                y -= 1;
+            }
+        }
+        n
+    }
+
+    /// Shift every line up one and fill the bottom line with garbage, leaving a hole in
+    /// the given column. Returns whether blocks were pushed off the top of the board.
+    pub fn add_garbage_line(&mut self, hole: usize) -> bool {
+        let overflowed = self.row(0).iter().any(|cell| cell.is_some());
+        let width = self.width;
+        self.cells.copy_within(width.., 0);
+        let bottom = self.cells.len() - width;
+        for (x, cell) in self.cells[bottom..].iter_mut().enumerate() {
+            *cell = if x == hole { None } else { Some(PieceColor::Gray) };
+        }
+        overflowed
+    }
+
+    /// Number of lines still holding garbage.
+    pub fn garbage_lines(&self) -> usize {
+        (0..self.total_height()).filter(|&y| self.row(y).contains(&Some(PieceColor::Gray))).count()
+    }
+
+    /// Remove given line and shift all the lines 'above' down.
+    pub fn remove_line(&mut self, n: usize) {
+        let width = self.width;
+        self.cells.copy_within(0..n * width, width);
+        self.cells[..width].fill(None);
+    }
+
+    /// Cells of the given row, from left to right.
+    fn row(&self, y: usize) -> &[Option<PieceColor>] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new(BOARD_WIDTH, BOARD_HEIGHT, 0)
+    }
+}
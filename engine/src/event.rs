@@ -0,0 +1,36 @@
+use crate::game::TopOut;
+use crate::piece::{Piece, PieceKind};
+use crate::scoring::{LineClear, TSpin};
+
+/// Something that happened in a game, for frontends to draw, play sounds for or count.
+/// Events pile up until `GameState::drain_events` takes them, which callers usually do
+/// after every `step` or `tick`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameEvent {
+    /// A new current piece entered the board, already turned if the initial rotation system applied.
+    PieceSpawned { piece: Piece },
+    /// The current piece shifted sideways or fell a row under gravity to the given position.
+    PieceMoved { x: i8, y: i8 },
+    /// The current piece turned into the given rotation state, using the rotation system's
+    /// kick with the given index, where `0` is the unkicked rotation.
+    Rotated { rotation_state: i8, kick: usize },
+    /// The current piece was soft dropped one row.
+    SoftDrop,
+    /// The current piece was hard dropped the given number of rows.
+    HardDrop { distance: u32 },
+    /// The current piece locked onto the stack with the given T-spin.
+    PieceLocked { piece: Piece, t_spin: TSpin },
+    /// A locked piece completed the given rows, counted like `Board::get`'s rows from the top.
+    /// They stay on the board for the ruleset's line clear delay.
+    LinesMarked { rows: Vec<usize> },
+    /// The given rows were removed and the stack above them fell into their place.
+    LinesCollapsed { rows: Vec<usize> },
+    /// A locked piece cleared the given rows or scored a T-spin, and how the clear was scored.
+    LinesCleared { rows: Vec<usize>, clear: LineClear },
+    /// A piece of the given kind went into the hold box.
+    Hold { kind: PieceKind },
+    /// The game reached the given level.
+    LevelUp { level: u32 },
+    /// The game ended, either by reaching its mode's goal or by topping out.
+    GameOver { completed: bool, top_out: Option<TopOut> }
+}
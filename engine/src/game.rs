@@ -0,0 +1,1113 @@
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::event::GameEvent;
+use crate::garbage::GarbageGenerator;
+use crate::input::{GameInput, HeldInputs};
+use crate::mode::GameMode;
+use crate::piece::{Piece, PieceKind};
+use crate::randomizer::Randomizer;
+use crate::rotation::RotationSystem;
+use crate::rules::{HoldMode, LockDelayMode, Ruleset, SpawnPosition, MAX_PREVIEW_COUNT};
+use crate::scoring::{detect_t_spin, LineClear, ScoringRule, TSpin};
+use crate::stats::GameStats;
+
+/// Simulated time that passes with every call to `GameState::tick`.
+pub const TICK: Duration = Duration::from_millis(1);
+
+/// Where a game is in its lifecycle.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PlayState {
+    /// Waiting on the title screen for the first game to start.
+    #[default]
+    Title,
+    /// A game is running and the clock advances with `step` and `tick`.
+    Playing,
+    /// A game is suspended. The clock is frozen and presses are ignored until it resumes.
+    Paused,
+    /// The last game topped out.
+    GameOver,
+    /// The player left a game for the menu.
+    Menu
+}
+
+/// What a running game is doing with the current piece.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Phase {
+    /// The current piece is on the board and can be moved.
+    #[default]
+    Active,
+    /// The last piece locked and completed lines that stay on the board for `remaining_ms`
+    /// more milliseconds before they are removed.
+    LineClear { remaining_ms: u64 },
+    /// The next piece enters once `remaining_ms` more milliseconds have passed.
+    Entry { remaining_ms: u64 }
+}
+
+/// How a game that ended without reaching its goal topped out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TopOut {
+    /// A new piece overlapped the stack as it entered the board.
+    BlockOut,
+    /// A piece locked entirely inside the hidden rows above the visible playfield.
+    LockOut,
+    /// Garbage pushed the stack off the top of the board.
+    GarbageOut
+}
+
+/// Main state of the game.
+#[derive(Clone, Debug)]
+pub struct GameState {
+    /// Current piece being manipulated on the board.
+    current_piece: Piece,
+    /// Upcoming pieces shown to the player, next first. The piece after the last one is
+    /// only drawn from the randomizer once it is needed.
+    next_pieces: Vec<Piece>,
+    /// Held piece that can be swapped out for during play.
+    hold_piece: Option<Piece>,
+    /// Lines cleared during play.
+    lines_cleared_count: i16,
+    /// Simulated milliseconds at which each line was cleared, in order.
+    line_times: Vec<u64>,
+    /// Score accumulated throughout play.
+    score: i32,
+    /// Has the player held the piece since the current piece has been dropped.
+    has_held_a_piece: bool,
+    /// Where the game is in its lifecycle.
+    play_state: PlayState,
+    /// What the running game is doing with the current piece.
+    phase: Phase,
+    /// T-spin scored by the piece whose lines are waiting out the line clear delay.
+    pending_t_spin: TSpin,
+    /// What the game is played for and when it ends.
+    mode: GameMode,
+    /// Did the game end by reaching the mode's goal rather than topping out.
+    completed: bool,
+    /// How the game topped out, once it has.
+    top_out: Option<TopOut>,
+    /// Simulated milliseconds since the game started.
+    elapsed_ms: u64,
+    /// Current level.
+    level: u32,
+    /// Fraction of a row gravity has pulled the current piece that has not yet been applied.
+    gravity_progress: f64,
+    /// Time handed to `step` that has not yet added up to a full tick.
+    unsimulated: Duration,
+    /// Board where pieces are placed/represented.
+    board: Board,
+    /// Rules the game is played with.
+    ruleset: Ruleset,
+    /// Kick index used by the last rotation, cleared once the piece moves.
+    last_kick: Option<usize>,
+    /// Milliseconds the current piece has spent resting on the stack since the lock delay was last reset.
+    lock_timer: u64,
+    /// Lock delay resets used by the current piece.
+    lock_resets: u32,
+    /// Lowest row the current piece has reached.
+    lowest_y: i8,
+    /// Movement inputs currently held down.
+    held: HeldInputs,
+    /// Consecutive line clears so far, or `None` if the last piece cleared nothing.
+    combo: Option<u32>,
+    /// Was the last line clear a tetris or T-spin clear.
+    back_to_back: bool,
+    /// The last piece that cleared lines or scored a T-spin.
+    last_clear: Option<LineClear>,
+    /// Pieces placed, tetrises and T-spins so far.
+    stats: GameStats,
+    /// Seed the piece sequence was started from.
+    seed: u64,
+    /// Source of garbage rows, in modes that add them.
+    garbage: Option<GarbageGenerator>,
+    /// Events not yet taken by `drain_events`.
+    events: Vec<GameEvent>,
+    /// Are events queued at all, off for callers that never drain them.
+    events_enabled: bool,
+    /// Source of upcoming pieces.
+    randomizer: Box<dyn Randomizer>
+}
+
+/// Main state of the game.
+impl GameState {
+    /// Constructor for the GameState struct, for an endless marathon game.
+    /// Games built with the same ruleset and seed share the same piece sequence.
+    pub fn new(ruleset: Ruleset, seed: u64) -> Self {
+        GameState::with_mode(ruleset, GameMode::Marathon, seed)
+    }
+
+    /// Constructor for a game of the given mode.
+    pub fn with_mode(ruleset: Ruleset, mode: GameMode, seed: u64) -> Self {
+        let ruleset = ruleset.normalized();
+        let mut randomizer = ruleset.randomizer.build(seed);
+        let board = Board::new(ruleset.board_width, ruleset.board_height, ruleset.hidden_rows);
+        let mut draw_piece = || spawn_piece(&board, &ruleset, randomizer.next_kind());
+        let current_piece = draw_piece();
+        let next_pieces = (0..ruleset.preview_count.min(MAX_PREVIEW_COUNT)).map(|_| draw_piece()).collect();
+        let garbage = match mode {
+            GameMode::Dig { messiness, .. } => Some(GarbageGenerator::new(seed, messiness)),
+            _ => None
+        };
+        let mut game = GameState {
+            lines_cleared_count: 0,
+            line_times: Vec::new(),
+            score: 0,
+            has_held_a_piece: false,
+            current_piece,
+            next_pieces,
+            hold_piece: None,
+            play_state: PlayState::Title,
+            phase: Phase::Active,
+            pending_t_spin: TSpin::None,
+            mode,
+            completed: false,
+            top_out: None,
+            elapsed_ms: 0,
+            level: ruleset.start_level,
+            gravity_progress: 0.0,
+            unsimulated: Duration::ZERO,
+            board,
+            ruleset,
+            last_kick: None,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_y: 0,
+            held: HeldInputs::default(),
+            combo: None,
+            back_to_back: false,
+            last_clear: None,
+            stats: GameStats::default(),
+            seed,
+            garbage,
+            events: Vec::new(),
+            events_enabled: true,
+            randomizer
+        };
+        game.fill_garbage();
+        game
+    }
+
+    /// Reset the board, score and pieces and begin a new game from the given seed,
+    /// keeping the current ruleset and mode.
+    pub fn start(&mut self, seed: u64) {
+        let events_enabled = self.events_enabled;
+        *self = GameState::with_mode(self.ruleset, self.mode, seed);
+        self.events_enabled = events_enabled;
+        self.play_state = PlayState::Playing;
+        self.reset_piece_state();
+        self.emit(GameEvent::PieceSpawned { piece: self.current_piece });
+    }
+
+    /// Suspend a running game, freezing its clock. Returns whether the game was running.
+    pub fn pause(&mut self) -> bool {
+        if self.play_state != PlayState::Playing {
+            return false;
+        }
+        self.play_state = PlayState::Paused;
+        self.unsimulated = Duration::ZERO;
+        true
+    }
+
+    /// Continue a paused game. Returns whether the game was paused.
+    pub fn resume(&mut self) -> bool {
+        if self.play_state != PlayState::Paused {
+            return false;
+        }
+        self.play_state = PlayState::Playing;
+        true
+    }
+
+    /// Leave the current game, if any, for the menu.
+    pub fn quit_to_menu(&mut self) {
+        self.play_state = PlayState::Menu;
+    }
+
+    /// Go back to the title screen.
+    pub fn return_to_title(&mut self) {
+        self.play_state = PlayState::Title;
+    }
+
+    /// Current piece being manipulated on the board. Outside the active phase this is the
+    /// piece that last locked, which is already part of the board.
+    pub fn current_piece(&self) -> &Piece { &self.current_piece }
+
+    /// Upcoming pieces, next first, as many as the ruleset's preview count.
+    pub fn next_pieces(&self) -> &[Piece] { &self.next_pieces }
+
+    /// Held piece that can be swapped out for during play.
+    pub fn hold_piece(&self) -> Option<&Piece> { self.hold_piece.as_ref() }
+
+    /// Board where pieces are placed/represented.
+    pub fn board(&self) -> &Board { &self.board }
+
+    /// Lines cleared during play.
+    pub fn lines_cleared_count(&self) -> i16 { self.lines_cleared_count }
+
+    /// Simulated milliseconds at which each line was cleared, so the time the `n`th line went is at index `n - 1`.
+    pub fn line_times(&self) -> &[u64] { &self.line_times }
+
+    /// Score accumulated throughout play.
+    pub fn score(&self) -> i32 { self.score }
+
+    /// Where the game is in its lifecycle.
+    pub fn play_state(&self) -> PlayState { self.play_state }
+
+    /// What the game is played for and when it ends.
+    pub fn mode(&self) -> GameMode { self.mode }
+
+    /// Did the game end by reaching the mode's goal rather than topping out.
+    pub fn completed(&self) -> bool { self.completed }
+
+    /// How the game topped out, or `None` if it is still going or reached its goal.
+    pub fn top_out(&self) -> Option<TopOut> { self.top_out }
+
+    /// Is a game running, neither paused nor over.
+    pub fn is_playing(&self) -> bool { self.play_state == PlayState::Playing }
+
+    /// What the running game is doing with the current piece.
+    pub fn phase(&self) -> Phase { self.phase }
+
+    /// Is the current piece on the board and free to move, rather than waiting out a line clear or entry delay.
+    pub fn is_piece_active(&self) -> bool { self.phase == Phase::Active }
+
+    /// Take every event that happened since the last call, oldest first.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, GameEvent> { self.events.drain(..) }
+
+    /// Turn event queueing on or off, on by default. Bots and headless runs that never call
+    /// `drain_events` turn it off so the queue does not grow for the whole game.
+    /// Turning it off drops any events still queued.
+    pub fn set_events_enabled(&mut self, enabled: bool) {
+        self.events_enabled = enabled;
+        if !enabled {
+            self.events.clear();
+        }
+    }
+
+    /// Current level, going up by one every `lines_per_level` lines.
+    pub fn level(&self) -> u32 { self.level }
+
+    /// The last piece that cleared lines or scored a T-spin.
+    pub fn last_clear(&self) -> Option<&LineClear> { self.last_clear.as_ref() }
+
+    /// Pieces placed, tetrises and T-spins so far.
+    pub fn stats(&self) -> &GameStats { &self.stats }
+
+    /// Rules the game is played with.
+    pub fn ruleset(&self) -> &Ruleset { &self.ruleset }
+
+    /// Kick index used by the last rotation, or `None` if the piece has moved since.
+    pub fn last_kick(&self) -> Option<usize> { self.last_kick }
+
+    /// Seed the piece sequence was started from.
+    pub fn seed(&self) -> u64 { self.seed }
+
+    /// Simulated milliseconds since the game started.
+    pub fn elapsed_ms(&self) -> u64 { self.elapsed_ms }
+
+    /// Advance the simulation by `dt`, running one tick for every whole `TICK` it covers.
+    /// Any remainder is carried over to the next call, so the same total time always
+    /// produces the same number of ticks. Time handed over while no game is running is dropped.
+    pub fn step(&mut self, dt: Duration) {
+        if !self.is_playing() {
+            return;
+        }
+        self.unsimulated += dt;
+        while self.unsimulated >= TICK {
+            self.unsimulated -= TICK;
+            self.tick();
+        }
+    }
+
+    /// Advance the simulation by exactly one `TICK`, applying gravity when it is due
+    /// and locking the piece once it has rested on the stack for the lock delay.
+    /// Between pieces the tick counts down the line clear or entry delay instead.
+    pub fn tick(&mut self) {
+        if !self.is_playing() {
+            return;
+        }
+        self.elapsed_ms += 1;
+
+        if self.is_piece_active() {
+            self.auto_shift();
+            self.apply_gravity();
+
+            if self.ruleset.lock_delay_mode != LockDelayMode::Gravity && self.is_grounded() {
+                self.lock_timer += 1;
+                if self.lock_timer >= self.ruleset.lock_delay_ms {
+                    self.after_drop_collision();
+                }
+            }
+        }
+        else {
+            self.count_down_delay();
+        }
+        if self.is_playing() {
+            self.check_goal();
+        }
+    }
+
+    /// End the game as completed if it has reached its mode's goal, which also locks out
+    /// any further input. Returns whether it did.
+    fn check_goal(&mut self) -> bool {
+        if !self.mode.goal_reached(self) {
+            return false;
+        }
+        self.completed = true;
+        self.play_state = PlayState::GameOver;
+        self.emit(GameEvent::GameOver { completed: true, top_out: None });
+        true
+    }
+
+    /// Let the piece fall as far as gravity has carried it this tick, which may be several rows.
+    /// Holding soft drop multiplies gravity by the soft drop factor and scores every row dropped.
+    /// Under `LockDelayMode::Gravity` a piece gravity can't pull any further locks.
+    fn apply_gravity(&mut self) {
+        let mut rows_per_ms = self.ruleset.gravity.rows_per_ms(self.level);
+        if self.held.down {
+            rows_per_ms *= self.ruleset.handling.soft_drop_factor.max(1) as f64;
+        }
+
+        self.gravity_progress += rows_per_ms;
+        while self.gravity_progress >= 1.0 {
+            self.gravity_progress -= 1.0;
+            if !self.move_down(self.held.down) {
+                if self.ruleset.lock_delay_mode == LockDelayMode::Gravity {
+                    self.after_drop_collision();
+                    return;
+                }
+                // Gravity does not build up while the piece rests on the stack.
+                self.gravity_progress = 0.0;
+                break;
+            }
+        }
+    }
+
+    /// Run one tick of the line clear or entry delay, moving on once it runs out.
+    fn count_down_delay(&mut self) {
+        // A held shift keeps charging DAS, so the next piece can move as soon as it enters.
+        if self.held.shift.is_some() {
+            self.held.das_timer = (self.held.das_timer + 1).min(self.ruleset.handling.das_ms);
+        }
+        match &mut self.phase {
+            Phase::Active => {}
+            Phase::LineClear { remaining_ms: 0..=1 } => self.clear_lines(self.pending_t_spin),
+            Phase::Entry { remaining_ms: 0..=1 } => self.spawn_next_piece(),
+            Phase::LineClear { remaining_ms } | Phase::Entry { remaining_ms } => *remaining_ms -= 1
+        }
+    }
+
+    /// Repeat a held left or right once DAS has charged, every ARR after that.
+    fn auto_shift(&mut self) {
+        let Some(direction) = self.held.shift else {
+            return;
+        };
+        let handling = self.ruleset.handling;
+
+        if self.held.das_timer < handling.das_ms {
+            self.held.das_timer += 1;
+            if self.held.das_timer < handling.das_ms {
+                return;
+            }
+            // DAS just charged, so the first repeat happens straight away.
+            self.held.arr_timer = handling.arr_ms;
+        }
+        else {
+            self.held.arr_timer += 1;
+        }
+
+        if handling.arr_ms == 0 {
+            while self.move_direction(direction) {}
+        }
+        else if self.held.arr_timer >= handling.arr_ms {
+            self.held.arr_timer = 0;
+            self.move_direction(direction);
+        }
+    }
+
+    /// A key mapped to `input` was pressed down.
+    /// Left, right and down act once now and keep repeating in `tick` until released;
+    /// everything else is a single `handle_input`. Rotations and hold are also remembered
+    /// until released, for the initial rotation and hold systems.
+    pub fn press(&mut self, input: GameInput) -> bool {
+        if !self.is_playing() {
+            return false;
+        }
+        match input {
+            GameInput::Left | GameInput::Right => {
+                if input == GameInput::Left {
+                    self.held.left = true;
+                }
+                else {
+                    self.held.right = true;
+                }
+                self.held.shift = Some(input);
+                self.held.das_timer = 0;
+                self.held.arr_timer = 0;
+            }
+            GameInput::Down => self.held.down = true,
+            GameInput::RotateRight | GameInput::RotateLeft => self.held.rotate = Some(input),
+            GameInput::Hold => self.held.hold = true,
+            _ => {}
+        }
+        self.handle_input(input)
+    }
+
+    /// A key mapped to `input` was released.
+    /// Releasing one direction while the other is still held shifts the other way with a fresh DAS.
+    pub fn release(&mut self, input: GameInput) {
+        match input {
+            GameInput::Left | GameInput::Right => {
+                let other = if input == GameInput::Left {
+                    self.held.left = false;
+                    (self.held.right, GameInput::Right)
+                }
+                else {
+                    self.held.right = false;
+                    (self.held.left, GameInput::Left)
+                };
+                if self.held.shift == Some(input) {
+                    self.held.shift = if other.0 { Some(other.1) } else { None };
+                    self.held.das_timer = 0;
+                    self.held.arr_timer = 0;
+                }
+            }
+            GameInput::Down => self.held.down = false,
+            GameInput::RotateRight | GameInput::RotateLeft if self.held.rotate == Some(input) => self.held.rotate = None,
+            GameInput::Hold => self.held.hold = false,
+            _ => {}
+        }
+    }
+
+    /// Apply a single player input to the current piece.
+    /// Returns whether the input was handled.
+    pub fn handle_input(&mut self, input: GameInput) -> bool {
+        if !self.is_playing() || !self.is_piece_active() {
+            return false;
+        }
+        match input {
+            GameInput::Down => self.move_down(true),
+            GameInput::Left | GameInput::Right => self.move_direction(input),
+            GameInput::RotateRight | GameInput::RotateLeft => self.rotate(input),
+            GameInput::HardDrop => self.hard_drop(),
+            GameInput::Hold => self.hold(),
+            GameInput::Start => false
+        }
+    }
+
+    /// Move a piece left, right or down one block.
+    pub fn move_direction(&mut self, direction: GameInput) -> bool {
+        let moved = self.shift_piece(direction);
+        if moved {
+            self.emit(GameEvent::PieceMoved { x: self.current_piece.x, y: self.current_piece.y });
+        }
+        moved
+    }
+
+    /// Move the piece left, right or down one block without reporting it.
+    fn shift_piece(&mut self, direction: GameInput) -> bool {
+        if !self.is_piece_active() {
+            return false;
+        }
+        let mut x: i8 = self.current_piece.x;
+        let mut y: i8 = self.current_piece.y;
+
+        match direction {
+            GameInput::Left => x -= 1,
+            GameInput::Right => x += 1,
+            GameInput::Down => y += 1,
+            _ => {}
+        }
+
+        if self.check_collision(x, y) {
+            return false;
+        }
+        let was_grounded = self.is_grounded();
+        self.current_piece.x = x;
+        self.current_piece.y = y;
+        self.last_kick = None;
+
+        if y > self.lowest_y {
+            // Reaching a new lowest row always gives the piece a fresh lock delay.
+            self.lowest_y = y;
+            self.lock_timer = 0;
+            self.lock_resets = 0;
+        }
+        else if direction != GameInput::Down {
+            self.extend_lock_delay(was_grounded);
+        }
+        true
+    }
+
+    /// Move the piece down one block. Resting on the stack does not lock the piece;
+    /// that is left to the lock delay.
+    pub fn move_down(&mut self, is_holding_down: bool) -> bool {
+        if !is_holding_down {
+            return self.move_direction(GameInput::Down);
+        }
+        let moved = self.shift_piece(GameInput::Down);
+        if moved {
+            self.score = self.score.saturating_add(self.scoring_rule().soft_drop(1));
+            self.emit(GameEvent::SoftDrop);
+        }
+        moved
+    }
+
+    /// Drop a piece straight down until collision and add score for each block passed.
+    pub fn hard_drop(&mut self) -> bool {
+        if !self.is_piece_active() {
+            return false;
+        }
+        let mut rows = 0;
+        while self.shift_piece(GameInput::Down) {
+            rows += 1;
+        }
+        self.emit(GameEvent::HardDrop { distance: rows });
+        self.score = self.score.saturating_add(self.scoring_rule().hard_drop(rows));
+        self.after_drop_collision();
+        true
+    }
+
+    /// Handle the current piece after a collision occurs from being dropped.
+    /// A piece locking entirely inside the hidden rows, or the next piece overlapping the
+    /// stack as it enters, ends the game. Completed lines are marked and stay on the board
+    /// for the line clear delay before they are removed.
+    pub fn after_drop_collision(&mut self) {
+        let t_spin = detect_t_spin(&self.board, &self.current_piece, self.last_kick);
+        let locked_out = self.current_piece.blocks().all(|(_, y)| self.board.is_hidden(y));
+        self.board.commit(&self.current_piece);
+        self.stats.pieces_placed += 1;
+        self.emit(GameEvent::PieceLocked { piece: self.current_piece, t_spin });
+        if locked_out {
+            self.end_with_top_out(TopOut::LockOut);
+            return;
+        }
+        let rows = self.board.full_lines();
+        if rows.is_empty() {
+            self.clear_lines(t_spin);
+            return;
+        }
+        self.emit(GameEvent::LinesMarked { rows });
+        if self.ruleset.line_clear_delay_ms == 0 {
+            self.clear_lines(t_spin);
+            return;
+        }
+        self.pending_t_spin = t_spin;
+        self.phase = Phase::LineClear { remaining_ms: self.ruleset.line_clear_delay_ms };
+    }
+
+    /// Remove the completed lines of the piece that last locked, then bring in the next
+    /// piece once the entry delay has passed.
+    fn clear_lines(&mut self, t_spin: TSpin) {
+        self.remove_lines(t_spin);
+        if self.check_goal() {
+            return;
+        }
+        if matches!(self.mode, GameMode::Dig { endless: true, .. }) && self.fill_garbage() {
+            self.end_with_top_out(TopOut::GarbageOut);
+            return;
+        }
+        if self.ruleset.entry_delay_ms == 0 {
+            self.spawn_next_piece();
+        }
+        else {
+            self.phase = Phase::Entry { remaining_ms: self.ruleset.entry_delay_ms };
+        }
+    }
+
+    /// Bring the next piece onto the board. With the initial hold system, hold held down
+    /// swaps it straight into the hold box.
+    fn spawn_next_piece(&mut self) {
+        self.phase = Phase::Active;
+        self.has_held_a_piece = false;
+        let mut piece = self.take_next_piece();
+        if self.ruleset.initial_hold && self.held.hold && self.can_hold() {
+            self.has_held_a_piece = true;
+            piece = self.swap_hold(piece);
+        }
+        self.enter(piece);
+    }
+
+    /// Bring `piece` onto the board as the current piece with fresh timers. With the initial
+    /// rotation system, a rotation held down turns it as it enters if there is room.
+    /// A piece that overlaps the stack ends the game.
+    fn enter(&mut self, piece: Piece) {
+        self.current_piece = piece;
+        if let Some(direction) = self.held.rotate.filter(|_| self.ruleset.initial_rotation) {
+            let clockwise = direction == GameInput::RotateRight;
+            if let Some((rotated, _)) = self.rotation_system().rotate(&self.board, &self.current_piece, clockwise) {
+                self.current_piece = rotated;
+            }
+        }
+        self.reset_piece_state();
+        self.emit(GameEvent::PieceSpawned { piece: self.current_piece });
+        if self.check_collision(self.current_piece.x, self.current_piece.y) {
+            self.end_with_top_out(TopOut::BlockOut);
+        }
+    }
+
+    /// End the game as topped out.
+    fn end_with_top_out(&mut self, top_out: TopOut) {
+        self.top_out = Some(top_out);
+        self.play_state = PlayState::GameOver;
+        self.emit(GameEvent::GameOver { completed: false, top_out: Some(top_out) });
+    }
+
+    /// Calculate what lines need removed and add score/remove lines accordingly.
+    /// Also keeps the combo and back-to-back chains up to date.
+    fn remove_lines(&mut self, t_spin: TSpin) {
+        let garbage_lines = self.board.garbage_lines();
+        let rows = self.board.full_lines();
+        let n = self.board.remove_lines();
+        if !rows.is_empty() {
+            self.emit(GameEvent::LinesCollapsed { rows: rows.clone() });
+        }
+        self.stats.garbage_cleared += (garbage_lines - self.board.garbage_lines()) as u32;
+        if n == 4 {
+            self.stats.tetrises += 1;
+        }
+        if t_spin != TSpin::None {
+            self.stats.t_spins += 1;
+        }
+        if n == 0 {
+            self.combo = None;
+            if t_spin == TSpin::None {
+                return;
+            }
+        }
+        else {
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+        }
+
+        let mut clear = LineClear {
+            lines: n as u32,
+            t_spin,
+            combo: self.combo.unwrap_or(0),
+            back_to_back: false,
+            perfect_clear: n > 0 && self.board.is_empty(),
+            level: self.level()
+        };
+        if n > 0 {
+            let difficult = clear.is_difficult();
+            clear.back_to_back = difficult && self.back_to_back;
+            self.back_to_back = difficult;
+        }
+
+        self.lines_cleared_count = self.lines_cleared_count.saturating_add(n);
+        self.line_times.extend(std::iter::repeat_n(self.elapsed_ms, n as usize));
+        self.score = self.score.saturating_add(self.scoring_rule().line_clear(&clear));
+        self.emit(GameEvent::LinesCleared { rows, clear });
+        if let Some(levels_gained) = (self.lines_cleared_count as u32).checked_div(self.ruleset.lines_per_level) {
+            let level = self.ruleset.start_level.saturating_add(levels_gained);
+            if level > self.level {
+                self.emit(GameEvent::LevelUp { level });
+            }
+            self.level = level;
+        }
+        self.last_clear = Some(clear);
+    }
+
+    /// Add garbage lines until the board holds as many as the mode starts with, which is
+    /// never more than leaves the top four rows free. Returns whether the stack was
+    /// pushed off the top of the board.
+    fn fill_garbage(&mut self) -> bool {
+        let GameMode::Dig { rows, .. } = self.mode else {
+            return false;
+        };
+        let Some(garbage) = self.garbage.as_mut() else {
+            return false;
+        };
+        let target = (rows as usize).min(self.board.height() - 4);
+        let mut overflowed = false;
+        for _ in self.board.garbage_lines()..target {
+            overflowed |= self.board.add_garbage_line(garbage.next_hole(self.board.width()));
+        }
+        overflowed
+    }
+
+    /// Can the current piece be swapped into the hold box right now, as the ruleset's hold mode allows.
+    pub fn can_hold(&self) -> bool {
+        match self.ruleset.hold_mode {
+            HoldMode::Disabled => false,
+            HoldMode::OncePerPiece => !self.has_held_a_piece,
+            HoldMode::Unlimited => true
+        }
+    }
+
+    /// Set current piece as the hold piece and swap out a new piece if there isn't one in the current hold.
+    /// Both pieces go back to their spawn position and orientation, and a swapped in piece
+    /// that overlaps the stack ends the game. Returns whether a swap happened.
+    pub fn hold(&mut self) -> bool {
+        if !self.can_hold() || !self.is_piece_active() {
+            return false;
+        }
+        self.has_held_a_piece = true;
+        let piece = self.swap_hold(self.current_piece);
+        if self.ruleset.hold_reset {
+            self.enter(piece);
+        }
+        else {
+            self.current_piece = piece;
+            self.reset_piece_state();
+            self.emit(GameEvent::PieceSpawned { piece });
+        }
+        true
+    }
+
+    /// Put `piece` in the hold box and return the piece that comes out in its place: the one
+    /// held before, or the next piece when the box was empty. With the ruleset's hold reset
+    /// both go back to their spawn position and orientation.
+    fn swap_hold(&mut self, piece: Piece) -> Piece {
+        let held = if self.ruleset.hold_reset { self.spawn(piece.kind) } else { piece };
+        self.emit(GameEvent::Hold { kind: piece.kind });
+        match self.hold_piece.replace(held) {
+            Some(previous) if self.ruleset.hold_reset => self.spawn(previous.kind),
+            Some(previous) => previous,
+            None => self.take_next_piece()
+        }
+    }
+
+    /// Rotates the given piece using the ruleset's rotation system, kicking it
+    /// into a free spot if the rotation system allows it.
+    pub fn rotate(&mut self, direction: GameInput) -> bool {
+        if !self.is_piece_active() {
+            return false;
+        }
+        let clockwise = match direction {
+            GameInput::RotateRight => true,
+            GameInput::RotateLeft => false,
+            _ => return false
+        };
+
+        match self.rotation_system().rotate(&self.board, &self.current_piece, clockwise) {
+            Some((piece, kick)) => {
+                let was_grounded = self.is_grounded();
+                self.current_piece = piece;
+                self.last_kick = Some(kick);
+                self.emit(GameEvent::Rotated { rotation_state: piece.rotation_state, kick });
+                if piece.y > self.lowest_y {
+                    self.lowest_y = piece.y;
+                    self.lock_timer = 0;
+                    self.lock_resets = 0;
+                }
+                else {
+                    self.extend_lock_delay(was_grounded);
+                }
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Is the current piece resting on the stack or the floor.
+    pub fn is_grounded(&self) -> bool {
+        self.check_collision(self.current_piece.x, self.current_piece.y + 1)
+    }
+
+    /// Reset or extend the lock delay after a shift or rotation, as the ruleset's
+    /// lock delay mode allows. Only moves made on the ground count.
+    fn extend_lock_delay(&mut self, was_grounded: bool) {
+        if !was_grounded && !self.is_grounded() {
+            return;
+        }
+        match self.ruleset.lock_delay_mode {
+            LockDelayMode::Infinity => self.lock_timer = 0,
+            LockDelayMode::MoveReset { limit } => {
+                if self.lock_resets < limit {
+                    self.lock_resets += 1;
+                    self.lock_timer = 0;
+                }
+                else {
+                    // Out of resets, so the piece locks as soon as it is on the ground.
+                    self.lock_timer = self.ruleset.lock_delay_ms;
+                }
+            }
+            LockDelayMode::StepReset | LockDelayMode::Gravity => {}
+        }
+    }
+
+    /// Queue an event for `drain_events`, unless events are turned off.
+    fn emit(&mut self, event: GameEvent) {
+        if self.events_enabled {
+            self.events.push(event);
+        }
+    }
+
+    /// Give a piece that just entered the board fresh gravity and lock timers.
+    fn reset_piece_state(&mut self) {
+        self.last_kick = None;
+        self.lock_timer = 0;
+        self.lock_resets = 0;
+        self.lowest_y = self.current_piece.y;
+        self.gravity_progress = 0.0;
+    }
+
+    /// Scoring rule selected by the ruleset.
+    fn scoring_rule(&self) -> &'static dyn ScoringRule {
+        self.ruleset.scoring.rule()
+    }
+
+    /// Rotation system selected by the ruleset.
+    fn rotation_system(&self) -> &'static dyn RotationSystem {
+        self.ruleset.rotation_system.system()
+    }
+
+    /// Construct a piece of the given kind in the ruleset's spawn orientation.
+    fn spawn(&self, kind: PieceKind) -> Piece {
+        spawn_piece(&self.board, &self.ruleset, kind)
+    }
+
+    /// Construct the next piece handed out by the randomizer.
+    fn next_from_randomizer(&mut self) -> Piece {
+        let kind = self.randomizer.next_kind();
+        self.spawn(kind)
+    }
+
+    /// Take the next piece off the front of the queue and top the queue back up.
+    fn take_next_piece(&mut self) -> Piece {
+        let piece = self.next_from_randomizer();
+        self.next_pieces.push(piece);
+        self.next_pieces.remove(0)
+    }
+
+    /// Calculates the y position of the drop shadow.
+    pub fn get_drop_shadow_y(&self) -> i8 {
+        let mut y: i8 = self.current_piece.y;
+        while !self.check_collision(self.current_piece.x, y) {
+            y += 1;
+        }
+        y - 1
+    }
+
+    /// Checks if the current piece collides with another block given a different x/y value.
+    pub fn check_collision(&self, x: i8, y: i8) -> bool {
+        self.board.collides(self.current_piece.get_rotation_state(), x, y)
+    }
+}
+
+/// A piece of the given kind in the ruleset's spawn orientation, placed where the ruleset
+/// has pieces enter `board`.
+fn spawn_piece(board: &Board, ruleset: &Ruleset, kind: PieceKind) -> Piece {
+    let rotation_system = ruleset.rotation_system.system();
+    let piece = Piece::new(kind, rotation_system.shapes(kind));
+    let mask = piece.get_rotation_state();
+    let x = match ruleset.spawn_position {
+        SpawnPosition::Standard => board.spawn_x(mask, rotation_system.spawn_column(kind)),
+        SpawnPosition::Legacy => (board.width() / 2 - 1).min(board.width() - 4) as i8
+    };
+    Piece { x, y: board.spawn_y(mask), ..piece }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::new(Ruleset::default(), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gravity::GravityCurve;
+    use crate::piece::PieceColor;
+    use crate::rules::Handling;
+
+    /// A marathon game under `ruleset` that has just started.
+    fn started(ruleset: Ruleset) -> GameState {
+        let mut game = GameState::new(ruleset, 1);
+        game.start(1);
+        game
+    }
+
+    /// Let the current piece fall onto the stack, then tap left and right in turn every 100 ms,
+    /// `taps` times, and return how many ticks after landing the piece locked.
+    fn ticks_until_lock(game: &mut GameState, taps: u64) -> u64 {
+        while !game.is_grounded() {
+            game.tick();
+        }
+        let placed = game.stats().pieces_placed;
+        for ticks in 1..=10_000 {
+            game.tick();
+            if game.stats().pieces_placed > placed {
+                return ticks;
+            }
+            if ticks % 100 == 0 && ticks / 100 <= taps {
+                let input = if ticks % 200 == 0 { GameInput::Right } else { GameInput::Left };
+                assert!(game.press(input), "tap {} did not move the piece", ticks / 100);
+                game.release(input);
+            }
+        }
+        panic!("the piece never locked");
+    }
+
+    /// Guideline rules with pieces dropping straight to the stack and the given lock delay mode.
+    fn lock_rules(lock_delay_mode: LockDelayMode) -> Ruleset {
+        Ruleset { gravity: GravityCurve::TwentyG, lock_delay_ms: 500, lock_delay_mode, ..Ruleset::guideline() }
+    }
+
+    #[test]
+    fn infinity_resets_the_lock_delay_on_every_move() {
+        // The last tap comes 1000 ms after landing and the piece locks a full lock delay later.
+        let mut game = started(lock_rules(LockDelayMode::Infinity));
+        assert_eq!(ticks_until_lock(&mut game, 10), 1500);
+    }
+
+    #[test]
+    fn move_reset_locks_once_the_resets_run_out() {
+        // Three taps reset the lock delay and the fourth, at 400 ms, locks the piece on the next tick.
+        let mut game = started(lock_rules(LockDelayMode::MoveReset { limit: 3 }));
+        assert_eq!(ticks_until_lock(&mut game, 10), 401);
+    }
+
+    #[test]
+    fn step_reset_ignores_moves_on_the_ground() {
+        // The tick the piece lands on counts towards the lock delay.
+        for taps in [0, 3] {
+            let mut game = started(lock_rules(LockDelayMode::StepReset));
+            assert_eq!(ticks_until_lock(&mut game, taps), 499);
+        }
+    }
+
+    #[test]
+    fn gravity_lock_waits_for_the_next_gravity_step() {
+        // The piece slides left and right on the stack and locks a level 1 gravity interval
+        // after it landed, however much it moves.
+        let mut game = started(Ruleset::classic());
+        let interval = (1.0 / game.ruleset().gravity.rows_per_ms(1)).ceil() as u64;
+        assert_eq!(ticks_until_lock(&mut game, 5), interval);
+    }
+
+    /// Guideline rules with the given DAS and ARR, and no delays between pieces unless a test sets them.
+    fn shift_rules(das_ms: u64, arr_ms: u64) -> Ruleset {
+        Ruleset { handling: Handling { das_ms, arr_ms, ..Ruleset::guideline().handling }, ..Ruleset::guideline() }
+    }
+
+    /// Run `ticks` ticks and return where the current piece ends up.
+    fn x_after(game: &mut GameState, ticks: u64) -> i8 {
+        for _ in 0..ticks {
+            game.tick();
+        }
+        game.current_piece().x
+    }
+
+    #[test]
+    fn held_shift_waits_for_das_then_repeats_every_arr() {
+        let mut game = started(shift_rules(100, 20));
+        let spawn_x = game.current_piece().x;
+        assert!(game.press(GameInput::Left));
+        assert_eq!(game.current_piece().x, spawn_x - 1);
+        assert_eq!(x_after(&mut game, 99), spawn_x - 1);
+        assert_eq!(x_after(&mut game, 1), spawn_x - 2);
+        assert_eq!(x_after(&mut game, 19), spawn_x - 2);
+        assert_eq!(x_after(&mut game, 1), spawn_x - 3);
+        game.release(GameInput::Left);
+        assert_eq!(x_after(&mut game, 100), spawn_x - 3);
+    }
+
+    #[test]
+    fn zero_arr_moves_straight_to_the_wall_once_das_charges() {
+        let mut walled = started(shift_rules(100, 0));
+        while walled.handle_input(GameInput::Right) {}
+        let wall_x = walled.current_piece().x;
+
+        let mut game = started(shift_rules(100, 0));
+        let spawn_x = game.current_piece().x;
+        game.press(GameInput::Right);
+        assert_eq!(x_after(&mut game, 99), spawn_x + 1);
+        assert_eq!(x_after(&mut game, 1), wall_x);
+    }
+
+    #[test]
+    fn releasing_one_direction_hands_over_to_the_other_with_a_fresh_das() {
+        let mut game = started(shift_rules(100, 20));
+        let spawn_x = game.current_piece().x;
+        game.press(GameInput::Left);
+        assert_eq!(x_after(&mut game, 50), spawn_x - 1);
+        // The latest direction pressed wins while both are held.
+        game.press(GameInput::Right);
+        assert_eq!(game.current_piece().x, spawn_x);
+        // Letting go of the direction that is not shifting changes nothing.
+        game.release(GameInput::Left);
+        assert_eq!(x_after(&mut game, 100), spawn_x + 1);
+        game.press(GameInput::Left);
+        assert_eq!(game.current_piece().x, spawn_x);
+        // Left goes back to Right, which has to charge DAS again before it repeats.
+        game.release(GameInput::Left);
+        assert_eq!(game.current_piece().x, spawn_x);
+        assert_eq!(x_after(&mut game, 99), spawn_x);
+        assert_eq!(x_after(&mut game, 1), spawn_x + 1);
+    }
+
+    #[test]
+    fn das_keeps_charging_through_the_entry_delay() {
+        let mut game = started(Ruleset { entry_delay_ms: 300, ..shift_rules(100, 20) });
+        assert!(game.press(GameInput::HardDrop));
+        assert!(!game.is_piece_active());
+        // Pressed during the delay, the shift has nothing to move yet but starts charging.
+        assert!(!game.press(GameInput::Right));
+        while !game.is_piece_active() {
+            game.tick();
+        }
+        // The entering piece repeats after ARR rather than waiting out DAS again.
+        let spawn_x = game.current_piece().x;
+        assert_eq!(x_after(&mut game, 19), spawn_x);
+        assert_eq!(x_after(&mut game, 1), spawn_x + 1);
+    }
+
+    /// Run ticks for as long as `in_phase` holds and return how many it took.
+    fn ticks_in(game: &mut GameState, in_phase: fn(Phase) -> bool) -> u64 {
+        let mut ticks = 0;
+        while in_phase(game.phase()) {
+            game.tick();
+            ticks += 1;
+        }
+        ticks
+    }
+
+    #[test]
+    fn classic_line_clears_wait_out_the_line_clear_and_entry_delays() {
+        let mut game = started(Ruleset::classic());
+        let bottom = game.board().total_height() as i8 - 1;
+        // Fill the bottom row around where the current piece lands, so dropping it completes the row.
+        let mut landed = *game.current_piece();
+        while !game.board().collides(landed.get_rotation_state(), landed.x, landed.y + 1) {
+            landed.y += 1;
+        }
+        for x in 0..game.board().width() as i8 {
+            if !landed.blocks().any(|block| block == (x, bottom)) {
+                game.board.set(x as usize, bottom as usize, Some(PieceColor::Gray));
+            }
+        }
+        game.drain_events();
+
+        assert!(game.press(GameInput::HardDrop));
+        assert_eq!(game.phase(), Phase::LineClear { remaining_ms: 333 });
+        let locked: Vec<GameEvent> = game.drain_events().collect();
+        assert!(matches!(locked.as_slice(), [GameEvent::HardDrop { .. }, GameEvent::PieceLocked { .. }, GameEvent::LinesMarked { rows }] if *rows == [bottom as usize]));
+
+        // The completed row stays on the board until the line clear delay runs out.
+        let bottom_full = |game: &GameState| (0..game.board().width() as i8).all(|x| game.board().is_solid(x, bottom));
+        assert!(bottom_full(&game));
+        assert_eq!(game.lines_cleared_count(), 0);
+        let clear_ticks = ticks_in(&mut game, |phase| matches!(phase, Phase::LineClear { .. }));
+        assert_eq!(clear_ticks, 333);
+        assert_eq!(game.lines_cleared_count(), 1);
+        let entry_ticks = ticks_in(&mut game, |phase| matches!(phase, Phase::Entry { .. }));
+        assert_eq!(entry_ticks, 167);
+        assert!(game.is_piece_active());
+
+        let cleared: Vec<GameEvent> = game.drain_events().collect();
+        assert!(matches!(cleared.as_slice(), [GameEvent::LinesCollapsed { rows }, GameEvent::LinesCleared { .. }, GameEvent::PieceSpawned { .. }] if *rows == [bottom as usize]));
+        assert!(!bottom_full(&game));
+    }
+
+    #[test]
+    fn disabled_events_stay_off_across_games() {
+        let mut game = GameState::new(Ruleset::guideline(), 1);
+        game.set_events_enabled(false);
+        game.start(1);
+        for _ in 0..10_000 {
+            game.tick();
+        }
+        game.start(2);
+        game.press(GameInput::HardDrop);
+        assert_eq!(game.drain_events().count(), 0);
+    }
+}
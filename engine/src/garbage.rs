@@ -0,0 +1,36 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Mixed into the game's seed so garbage holes don't follow the piece sequence.
+const SEED_SALT: u64 = 0x6761_7262_6167_6521;
+
+/// Picks the hole column of every garbage row a dig game adds to the board.
+#[derive(Clone, Debug)]
+pub struct GarbageGenerator {
+    /// Percent chance a row's hole moves away from the hole of the row before it.
+    messiness: u32,
+    /// Hole of the last row handed out, or `None` before the first.
+    hole: Option<usize>,
+    /// Seeded generator driving the holes.
+    rng: ChaCha8Rng
+}
+
+impl GarbageGenerator {
+    /// Construct a generator for rows with the given messiness, in percent.
+    /// Generators built from the same seed and messiness hand out the same holes.
+    pub fn new(seed: u64, messiness: u32) -> Self {
+        GarbageGenerator { messiness: messiness.min(100), hole: None, rng: ChaCha8Rng::seed_from_u64(seed ^ SEED_SALT) }
+    }
+
+    /// Hole column of the next garbage row on a board `width` columns wide.
+    pub fn next_hole(&mut self, width: usize) -> usize {
+        let hole = match self.hole {
+            Some(hole) if self.rng.gen_range(0..100) >= self.messiness => hole,
+            // A moved hole never lands back in the same column.
+            Some(hole) => (hole + self.rng.gen_range(1..width)) % width,
+            None => self.rng.gen_range(0..width)
+        };
+        self.hole = Some(hole);
+        hole
+    }
+}
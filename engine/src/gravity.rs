@@ -0,0 +1,54 @@
+/// Frame rate the NES and TGM gravity tables are measured in.
+const FRAMES_PER_SECOND: f64 = 60.0988;
+
+/// 20 rows a frame, enough to reach the floor of any board the moment a piece appears.
+const TWENTY_G: f64 = 20.0 * FRAMES_PER_SECOND / 1000.0;
+
+/// Frames per row on the NES, indexed by NES level (ours minus one). Level 29 and up is one frame per row.
+const NES_FRAMES_PER_ROW: [u32; 29] = [48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2];
+
+/// Selectable gravity tables, mapping the level to how fast pieces fall.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GravityCurve {
+    /// Guideline curve of `(0.8 - (level - 1) * 0.007)^(level - 1)` seconds per row, capped at 20G.
+    #[default]
+    Guideline,
+    /// NES frames-per-row table.
+    Classic,
+    /// TGM style 20G, where pieces drop to the floor the moment they appear.
+    TwentyG
+}
+
+impl GravityCurve {
+    /// Every gravity curve, in declaration order.
+    pub const ALL: [GravityCurve; 3] = [GravityCurve::Guideline, GravityCurve::Classic, GravityCurve::TwentyG];
+
+    /// Rows a piece falls per millisecond at the given level. Values above 1 fall several rows a tick.
+    pub fn rows_per_ms(self, level: u32) -> f64 {
+        match self {
+            GravityCurve::Guideline => {
+                let n = (level.clamp(1, 20) - 1) as f64;
+                (1.0 / ((0.8 - n * 0.007).powf(n) * 1000.0)).min(TWENTY_G)
+            }
+            GravityCurve::Classic => {
+                let frames = NES_FRAMES_PER_ROW.get(level.saturating_sub(1) as usize).copied().unwrap_or(1);
+                FRAMES_PER_SECOND / (frames as f64 * 1000.0)
+            }
+            GravityCurve::TwentyG => TWENTY_G
+        }
+    }
+
+    /// Short stable name of the curve.
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityCurve::Guideline => "guideline",
+            GravityCurve::Classic => "classic",
+            GravityCurve::TwentyG => "20g"
+        }
+    }
+
+    /// Parse a name produced by `GravityCurve::name`.
+    pub fn from_name(name: &str) -> Option<GravityCurve> {
+        GravityCurve::ALL.into_iter().find(|curve| curve.name() == name)
+    }
+}
@@ -0,0 +1,67 @@
+/// Next we create an enum that will represent all the possible
+/// inputs our piece needs to handle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameInput {
+    Down,
+    Left,
+    Right,
+    HardDrop,
+    RotateRight,
+    RotateLeft,
+    Hold,
+    Start
+}
+
+impl GameInput {
+    /// Every input, in declaration order.
+    pub const ALL: [GameInput; 8] = [
+        GameInput::Down,
+        GameInput::Left,
+        GameInput::Right,
+        GameInput::HardDrop,
+        GameInput::RotateRight,
+        GameInput::RotateLeft,
+        GameInput::Hold,
+        GameInput::Start
+    ];
+
+    /// Short stable name of the input.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameInput::Down => "down",
+            GameInput::Left => "left",
+            GameInput::Right => "right",
+            GameInput::HardDrop => "hard-drop",
+            GameInput::RotateRight => "rotate-right",
+            GameInput::RotateLeft => "rotate-left",
+            GameInput::Hold => "hold",
+            GameInput::Start => "start"
+        }
+    }
+
+    /// Parse a name produced by `GameInput::name`.
+    pub fn from_name(name: &str) -> Option<GameInput> {
+        GameInput::ALL.into_iter().find(|input| input.name() == name)
+    }
+}
+
+/// Inputs currently held down and the auto shift timers they drive.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct HeldInputs {
+    /// Is left held.
+    pub left: bool,
+    /// Is right held.
+    pub right: bool,
+    /// Direction being auto shifted, the most recently pressed of left and right.
+    pub shift: Option<GameInput>,
+    /// Milliseconds the shift direction has been held, up to the DAS.
+    pub das_timer: u64,
+    /// Milliseconds since the last auto repeat shift.
+    pub arr_timer: u64,
+    /// Is soft drop held.
+    pub down: bool,
+    /// Rotation held down, the most recently pressed of the two.
+    pub rotate: Option<GameInput>,
+    /// Is hold held.
+    pub hold: bool
+}
@@ -0,0 +1,45 @@
+//! Headless Tetris engine.
+//!
+//! Holds the board, the pieces, the hold/next queue and scoring without any
+//! knowledge of how the game is drawn, so frontends, bots and tests can all
+//! drive the same `GameState`.
+//!
+//! Everything that happens in a game, from a piece spawning to the game ending, is also
+//! reported as a `GameEvent` that callers take with `GameState::drain_events`.
+//!
+//! The engine never reads the wall clock. Time only moves when the caller
+//! invokes `GameState::step` or `GameState::tick`, which makes every game
+//! reproducible and lets simulations run as fast as the machine allows.
+//! Only `new_seed`, which frontends call to pick a seed, looks at the clock.
+//!
+//! The frontends share their command line: `Ruleset::apply_arg` and
+//! `GameMode::apply_arg` handle the flags that pick the rules and the mode.
+
+mod board;
+mod event;
+mod game;
+mod garbage;
+mod gravity;
+mod input;
+mod mode;
+mod piece;
+mod randomizer;
+pub mod replay;
+mod rotation;
+mod rules;
+mod scoring;
+mod stats;
+
+pub use board::{Board, BOARD_HEIGHT, BOARD_HEIGHT_RANGE, BOARD_WIDTH, BOARD_WIDTH_RANGE, HIDDEN_ROWS_RANGE};
+pub use event::GameEvent;
+pub use game::{GameState, Phase, PlayState, TopOut, TICK};
+pub use gravity::GravityCurve;
+pub use input::GameInput;
+pub use mode::{format_time, GameMode};
+pub use piece::{mask_cells, Piece, PieceColor, PieceKind};
+pub use replay::{Replay, ReplayAction, ReplayError, ReplayPlayer};
+pub use randomizer::{new_seed, BagRandomizer, HistoryRandomizer, Randomizer, RandomizerKind, UniformRandomizer};
+pub use rotation::{Ars, Classic, RotationSystem, RotationSystemKind, Srs};
+pub use rules::{Handling, HoldMode, LockDelayMode, Ruleset, SpawnPosition, MAX_PREVIEW_COUNT, START_LEVEL_RANGE};
+pub use scoring::{detect_t_spin, ClassicScoring, GuidelineScoring, LineClear, ScoringKind, ScoringRule, TSpin};
+pub use stats::GameStats;
@@ -0,0 +1,166 @@
+use crate::game::GameState;
+
+/// What a game is played for and when it ends.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GameMode {
+    /// Endless play for score until the stack tops out.
+    #[default]
+    Marathon,
+    /// Clear `lines` lines as fast as possible.
+    Sprint { lines: u32 },
+    /// Score as much as possible before `seconds` run out.
+    Ultra { seconds: u32 },
+    /// Dig through `rows` rows of garbage as fast as possible. `messiness` is the percent
+    /// chance each garbage row's hole moves away from the hole of the row below it. An
+    /// `endless` dig tops the garbage back up to `rows` as it is cleared and never finishes.
+    Dig { rows: u32, messiness: u32, endless: bool }
+}
+
+impl GameMode {
+    /// Every mode offered in menus, in the order they are listed.
+    pub const ALL: [GameMode; 8] = [
+        GameMode::Marathon,
+        GameMode::Sprint { lines: 20 },
+        GameMode::Sprint { lines: 40 },
+        GameMode::Sprint { lines: 100 },
+        GameMode::Ultra { seconds: 120 },
+        GameMode::Ultra { seconds: 180 },
+        GameMode::Dig { rows: 10, messiness: 50, endless: false },
+        GameMode::Dig { rows: 10, messiness: 50, endless: true }
+    ];
+
+    /// Short stable name of the mode, without its settings.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "marathon",
+            GameMode::Sprint { .. } => "sprint",
+            GameMode::Ultra { .. } => "ultra",
+            GameMode::Dig { .. } => "dig"
+        }
+    }
+
+    /// Parse a name produced by `GameMode::name`. Sprint gets the standard 40 lines,
+    /// ultra the standard 3 minutes and dig 10 rows of half messy garbage.
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        match name {
+            "marathon" => Some(GameMode::Marathon),
+            "sprint" => Some(GameMode::Sprint { lines: 40 }),
+            "ultra" => Some(GameMode::Ultra { seconds: 180 }),
+            "dig" => Some(GameMode::Dig { rows: 10, messiness: 50, endless: false }),
+            _ => None
+        }
+    }
+
+    /// The numbers that tune the mode: sprint's line target, ultra's time limit in seconds,
+    /// or dig's rows, messiness and `1` if it is endless.
+    pub fn settings(self) -> Vec<u32> {
+        match self {
+            GameMode::Marathon => Vec::new(),
+            GameMode::Sprint { lines } => vec![lines],
+            GameMode::Ultra { seconds } => vec![seconds],
+            GameMode::Dig { rows, messiness, endless } => vec![rows, messiness, endless as u32]
+        }
+    }
+
+    /// The same mode tuned with the given settings, in the order `GameMode::settings` lists them.
+    /// Settings left out keep their current values.
+    pub fn with_settings(self, values: &[u32]) -> GameMode {
+        let value = |index: usize, current: u32| values.get(index).copied().unwrap_or(current);
+        match self {
+            GameMode::Marathon => self,
+            GameMode::Sprint { lines } => GameMode::Sprint { lines: value(0, lines) },
+            GameMode::Ultra { seconds } => GameMode::Ultra { seconds: value(0, seconds) },
+            GameMode::Dig { rows, messiness, endless } => GameMode::Dig {
+                rows: value(0, rows),
+                messiness: value(1, messiness).min(100),
+                endless: value(2, endless as u32) != 0
+            }
+        }
+    }
+
+    /// Name of the mode together with its settings, such as `sprint-40`.
+    /// Games are only compared against others of the same category.
+    pub fn category(self) -> String {
+        match self {
+            GameMode::Dig { rows, messiness, endless: true } => format!("{}-endless-{}-{}", self.name(), rows, messiness),
+            _ => self.settings().iter().fold(self.name().to_string(), |category, value| format!("{}-{}", category, value))
+        }
+    }
+
+    /// Name of the mode for menus and headings.
+    pub fn label(self) -> String {
+        match self {
+            GameMode::Marathon => "Marathon".to_string(),
+            GameMode::Sprint { lines } => format!("Sprint {}", lines),
+            GameMode::Ultra { seconds } => format!("Ultra {}:{:02}", seconds / 60, seconds % 60),
+            GameMode::Dig { rows, endless: false, .. } => format!("Dig {}", rows),
+            GameMode::Dig { endless: true, .. } => "Endless Dig".to_string()
+        }
+    }
+
+    /// Milliseconds the game lasts before it ends, for modes played against the clock.
+    pub fn time_limit_ms(self) -> Option<u64> {
+        match self {
+            GameMode::Ultra { seconds } => Some(seconds as u64 * 1000),
+            _ => None
+        }
+    }
+
+    /// Are results of this mode ranked by the fastest time rather than the highest score.
+    pub fn ranks_by_time(self) -> bool {
+        matches!(self, GameMode::Sprint { .. } | GameMode::Dig { endless: false, .. })
+    }
+
+    /// Has the game reached the mode's goal.
+    pub fn goal_reached(self, game: &GameState) -> bool {
+        match self {
+            GameMode::Marathon | GameMode::Dig { endless: true, .. } => false,
+            GameMode::Sprint { lines } => game.lines_cleared_count() as u32 >= lines,
+            GameMode::Ultra { .. } => self.time_limit_ms().is_some_and(|limit| game.elapsed_ms() >= limit),
+            GameMode::Dig { endless: false, .. } => game.board().garbage_lines() == 0
+        }
+    }
+
+    /// Apply a command line flag the frontends share and return whether `flag` is one of them.
+    /// `--mode` picks the mode with its standard settings, and `--sprint-lines`, `--ultra-seconds`,
+    /// `--dig-rows`, `--messiness` and `--endless` tune it, so they should come after it.
+    /// Settings for another mode and values out of range are ignored.
+    pub fn apply_arg(&mut self, flag: &str, value: &str) -> bool {
+        let mode = *self;
+        match flag {
+            "--mode" => *self = GameMode::from_name(value).unwrap_or(mode),
+            "--sprint-lines" => {
+                if let (GameMode::Sprint { .. }, Ok(lines @ 1..)) = (mode, value.parse()) {
+                    *self = mode.with_settings(&[lines]);
+                }
+            }
+            "--ultra-seconds" => {
+                if let (GameMode::Ultra { .. }, Ok(seconds @ 1..)) = (mode, value.parse()) {
+                    *self = mode.with_settings(&[seconds]);
+                }
+            }
+            "--dig-rows" => {
+                if let (GameMode::Dig { messiness, endless, .. }, Ok(rows @ 1..)) = (mode, value.parse()) {
+                    *self = GameMode::Dig { rows, messiness, endless };
+                }
+            }
+            "--messiness" => {
+                if let (GameMode::Dig { rows, endless, .. }, Ok(messiness @ 0..=100)) = (mode, value.parse()) {
+                    *self = GameMode::Dig { rows, messiness, endless };
+                }
+            }
+            "--endless" => {
+                if let GameMode::Dig { rows, messiness, .. } = mode {
+                    *self = GameMode::Dig { rows, messiness, endless: value == "on" };
+                }
+            }
+            _ => return false
+        }
+        true
+    }
+}
+
+/// Format milliseconds of play as `minutes:seconds.milliseconds`.
+pub fn format_time(ms: u64) -> String {
+    format!("{}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
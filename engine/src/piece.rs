@@ -0,0 +1,99 @@
+/// Different colors a piece can be.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PieceColor {
+    /// Z
+    Red,
+    /// O
+    Yellow,
+    /// J
+    Blue,
+    /// I
+    Cyan,
+    /// L
+    Orange,
+    /// S
+    Green,
+    /// T
+    Purple,
+    /// None
+    Black,
+    /// Garbage and drop shadow.
+    Gray
+}
+
+/// The seven tetrominoes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    I,
+    J,
+    L,
+    O,
+    S,
+    T,
+    Z
+}
+
+impl PieceKind {
+    /// Every piece kind, in the order randomizers draw from.
+    pub const ALL: [PieceKind; 7] = [PieceKind::I, PieceKind::J, PieceKind::L, PieceKind::O, PieceKind::S, PieceKind::T, PieceKind::Z];
+
+    /// Color the piece is drawn with.
+    pub fn color(self) -> PieceColor {
+        match self {
+            PieceKind::I => PieceColor::Cyan,
+            PieceKind::J => PieceColor::Blue,
+            PieceKind::L => PieceColor::Orange,
+            PieceKind::O => PieceColor::Yellow,
+            PieceKind::S => PieceColor::Green,
+            PieceKind::T => PieceColor::Purple,
+            PieceKind::Z => PieceColor::Red
+        }
+    }
+}
+
+/// Piece struct.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Piece {
+    /// Which tetromino this piece is.
+    pub kind: PieceKind,
+    /// Current Rotate this piece is in.
+    pub rotation_state: i8,
+    /// X coordinate of the piece with respect to the board.
+    pub x: i8,
+    /// Y coordinate of the piece with respect to the board.
+    pub y: i8,
+    /// Array of the possible rotations of the piece.
+    pub rotation: [u32; 4],
+    /// Piece color when represented on the board.
+    pub piece_color: PieceColor
+}
+
+/// Tetris piece implementation.
+impl Piece {
+    pub fn new(kind: PieceKind, rotation: [u32; 4]) -> Self {
+        Piece {
+            kind,
+            rotation_state: 0,
+            x: 4,
+            y: 0,
+            rotation,
+            piece_color: kind.color()
+        }
+    }
+
+    /// Helper function to grab the current rotation the piece is on.
+    pub fn get_rotation_state(&self) -> u32 { self.rotation[self.rotation_state as usize] }
+
+    /// Board coordinates of every block of the piece in its current position and rotation.
+    pub fn blocks(&self) -> impl Iterator<Item = (i8, i8)> {
+        let (x, y) = (self.x, self.y);
+        mask_cells(self.get_rotation_state()).map(move |(dx, dy)| (x + dx, y + dy))
+    }
+}
+
+/// Offsets of the set bits of a 4x4 rotation mask, read row by row from the top left.
+pub fn mask_cells(mask: u32) -> impl Iterator<Item = (i8, i8)> {
+    (0..16i8)
+        .filter(move |i| (mask & (0x8000 >> i)) > 0)
+        .map(|i| (i % 4, i / 4))
+}
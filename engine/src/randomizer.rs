@@ -0,0 +1,174 @@
+use std::fmt::Debug;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::piece::PieceKind;
+
+/// Source of the piece sequence.
+///
+/// Every implementation is driven by a seeded generator, so two randomizers of the
+/// same kind built from the same seed always hand out the same pieces.
+pub trait Randomizer: Debug {
+    /// Draw the next piece of the sequence.
+    fn next_kind(&mut self) -> PieceKind;
+
+    /// Clone the randomizer, including its position in the sequence.
+    fn box_clone(&self) -> Box<dyn Randomizer>;
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Selectable randomizer strategies.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RandomizerKind {
+    /// Every piece is drawn independently with equal odds.
+    #[default]
+    Uniform,
+    /// Guideline randomizer, dealing out shuffled bags of all seven pieces.
+    SevenBag,
+    /// Same as `SevenBag` but every bag holds two of each piece.
+    FourteenBag,
+    /// TGM style randomizer that rerolls pieces found in the last four drawn.
+    History
+}
+
+impl RandomizerKind {
+    /// Every strategy, in declaration order.
+    pub const ALL: [RandomizerKind; 4] = [RandomizerKind::Uniform, RandomizerKind::SevenBag, RandomizerKind::FourteenBag, RandomizerKind::History];
+
+    /// Construct the randomizer for this strategy from the given seed.
+    pub fn build(self, seed: u64) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::Uniform => Box::new(UniformRandomizer::new(seed)),
+            RandomizerKind::SevenBag => Box::new(BagRandomizer::new(seed, 1)),
+            RandomizerKind::FourteenBag => Box::new(BagRandomizer::new(seed, 2)),
+            RandomizerKind::History => Box::new(HistoryRandomizer::new(seed, 4))
+        }
+    }
+
+    /// Short stable name of the strategy.
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::Uniform => "uniform",
+            RandomizerKind::SevenBag => "7-bag",
+            RandomizerKind::FourteenBag => "14-bag",
+            RandomizerKind::History => "history"
+        }
+    }
+
+    /// Parse a name produced by `RandomizerKind::name`.
+    pub fn from_name(name: &str) -> Option<RandomizerKind> {
+        RandomizerKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// Seed taken from the system clock, for frontends starting games without an explicit one.
+pub fn new_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0)
+}
+
+/// Every piece is drawn independently with equal odds.
+#[derive(Clone, Debug)]
+pub struct UniformRandomizer {
+    rng: ChaCha8Rng
+}
+
+impl UniformRandomizer {
+    pub fn new(seed: u64) -> Self {
+        UniformRandomizer { rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+}
+
+impl Randomizer for UniformRandomizer {
+    fn next_kind(&mut self) -> PieceKind {
+        PieceKind::ALL[self.rng.gen_range(0..PieceKind::ALL.len())]
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> { Box::new(self.clone()) }
+}
+
+/// Deals pieces out of shuffled bags holding `copies` of each piece, refilling once empty.
+#[derive(Clone, Debug)]
+pub struct BagRandomizer {
+    rng: ChaCha8Rng,
+    /// Copies of each piece put in a fresh bag.
+    copies: usize,
+    /// Pieces left in the current bag, drawn from the back.
+    bag: Vec<PieceKind>
+}
+
+impl BagRandomizer {
+    pub fn new(seed: u64, copies: usize) -> Self {
+        BagRandomizer { rng: ChaCha8Rng::seed_from_u64(seed), copies, bag: Vec::new() }
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn next_kind(&mut self) -> PieceKind {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend_from_slice(&PieceKind::ALL);
+            }
+            self.bag.shuffle(&mut self.rng);
+        }
+        self.bag.pop().unwrap_or(PieceKind::I)
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> { Box::new(self.clone()) }
+}
+
+/// TGM style randomizer. A drawn piece that is one of the last four is rerolled up
+/// to `rerolls` times, and the first piece is never an S, Z or O.
+#[derive(Clone, Debug)]
+pub struct HistoryRandomizer {
+    rng: ChaCha8Rng,
+    /// How many times a piece found in the history is redrawn.
+    rerolls: u32,
+    /// The last four pieces handed out, oldest first.
+    history: [PieceKind; 4],
+    /// Has the first piece been handed out yet.
+    started: bool
+}
+
+impl HistoryRandomizer {
+    pub fn new(seed: u64, rerolls: u32) -> Self {
+        HistoryRandomizer {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            rerolls,
+            history: [PieceKind::Z; 4],
+            started: false
+        }
+    }
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn next_kind(&mut self) -> PieceKind {
+        let kind = if self.started {
+            let mut kind = PieceKind::ALL[self.rng.gen_range(0..PieceKind::ALL.len())];
+            for _ in 0..self.rerolls {
+                if !self.history.contains(&kind) {
+                    break;
+                }
+                kind = PieceKind::ALL[self.rng.gen_range(0..PieceKind::ALL.len())];
+            }
+            kind
+        }
+        else {
+            self.started = true;
+            *[PieceKind::I, PieceKind::J, PieceKind::L, PieceKind::T].choose(&mut self.rng).unwrap_or(&PieceKind::I)
+        };
+
+        self.history.rotate_left(1);
+        self.history[3] = kind;
+        kind
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> { Box::new(self.clone()) }
+}
@@ -0,0 +1,388 @@
+//! Replay recording and playback.
+//!
+//! A replay is the seed, the ruleset, the game mode and every press and release the player made,
+//! stamped with the simulated millisecond it happened on. Because the engine is
+//! deterministic, feeding those inputs back at the same times rebuilds the exact
+//! same game. The file format is documented in the README under "Replay format".
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::game::{GameState, TICK};
+use crate::gravity::GravityCurve;
+use crate::input::GameInput;
+use crate::mode::GameMode;
+use crate::randomizer::RandomizerKind;
+use crate::rotation::RotationSystemKind;
+use crate::rules::{HoldMode, LockDelayMode, Ruleset, SpawnPosition};
+use crate::scoring::ScoringKind;
+
+/// Version written to the first line of every replay file.
+/// Version 1 files have no `mode` line and are read as marathon games,
+/// versions 1 and 2 have no `board` line and are played on a standard 10x20 board,
+/// versions 1 to 3 have no `hidden-rows` line and are played without hidden rows,
+/// versions 1 to 4 have no `preview` line and show a single next piece,
+/// versions 1 to 5 have no `hold` line and allow one hold per piece,
+/// versions 1 to 6 have no `spawn`, `initial-rotation` or `initial-hold` lines and are
+/// played with the legacy spawn position and without the initial rotation and hold systems,
+/// versions 1 to 7 have no `entry-delay` or `line-clear-delay` lines and bring the next
+/// piece in as soon as one locks, and versions 1 to 8 have no `hold-reset` line. Versions 1 to 5
+/// also bring held pieces back where they were held rather than at their spawn position.
+pub const REPLAY_VERSION: u32 = 9;
+
+/// What the player did at a point in a replay.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReplayAction {
+    /// A key mapped to the input went down.
+    Press(GameInput),
+    /// A key mapped to the input came back up.
+    Release(GameInput)
+}
+
+/// A single timestamped input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReplayEvent {
+    /// Simulated millisecond of the game the input was applied on.
+    pub time_ms: u64,
+    /// What the player did.
+    pub action: ReplayAction
+}
+
+/// Final score and line count a replay is expected to reproduce.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReplayResult {
+    pub score: i32,
+    pub lines_cleared_count: i16
+}
+
+/// Everything needed to play a game back exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    /// Seed the game was started from.
+    pub seed: u64,
+    /// Rules the game was played with.
+    pub ruleset: Ruleset,
+    /// Mode the game was played in.
+    pub mode: GameMode,
+    /// Every input of the game, in order.
+    pub events: Vec<ReplayEvent>,
+    /// Simulated millisecond the recording stopped on.
+    pub end_ms: u64,
+    /// How the recorded game finished, if it was written down.
+    pub result: Option<ReplayResult>
+}
+
+/// Errors raised while reading or writing a replay file.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The file could not be read or written.
+    Io(io::Error),
+    /// The file was written by a newer, unknown version of the format.
+    UnsupportedVersion(u32),
+    /// A line of the file could not be understood.
+    Parse { line: usize, message: String }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "replay i/o error: {}", error),
+            ReplayError::UnsupportedVersion(version) => write!(f, "unsupported replay version {}", version),
+            ReplayError::Parse { line, message } => write!(f, "replay line {}: {}", line, message)
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl Replay {
+    /// Start an empty recording of a game begun with `GameState::start(seed)`.
+    pub fn new(seed: u64, ruleset: Ruleset, mode: GameMode) -> Self {
+        Replay { seed, ruleset, mode, events: Vec::new(), end_ms: 0, result: None }
+    }
+
+    /// Append an input applied when the game's `elapsed_ms` was `time_ms`.
+    pub fn record(&mut self, time_ms: u64, action: ReplayAction) {
+        self.events.push(ReplayEvent { time_ms, action });
+    }
+
+    /// Stop the recording at the game's current time and remember how it stood.
+    pub fn finish(&mut self, game: &GameState) {
+        self.end_ms = game.elapsed_ms();
+        self.result = Some(ReplayResult { score: game.score(), lines_cleared_count: game.lines_cleared_count() });
+    }
+
+    /// Play the whole replay back headlessly and return the final game.
+    pub fn play(&self) -> GameState {
+        let mut player = ReplayPlayer::new(self.clone());
+        let mut game = player.start();
+        while !player.is_finished(&game) {
+            player.tick(&mut game);
+            // Nobody watches a replay played this way, so its events are dropped as they come.
+            game.drain_events();
+        }
+        game
+    }
+
+    /// Write the replay to a file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read a replay from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, ReplayError> {
+        Replay::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Write the replay in the text format described in the README.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let ruleset = &self.ruleset;
+        writeln!(writer, "tetris-replay {}", REPLAY_VERSION)?;
+        writeln!(writer, "seed {}", self.seed)?;
+        writeln!(writer, "randomizer {}", ruleset.randomizer.name())?;
+        writeln!(writer, "rotation {}", ruleset.rotation_system.name())?;
+        writeln!(writer, "lock-delay {}", ruleset.lock_delay_ms)?;
+        match ruleset.lock_delay_mode {
+            LockDelayMode::MoveReset { limit } => writeln!(writer, "lock-mode move {}", limit)?,
+            mode => writeln!(writer, "lock-mode {}", mode.name())?
+        }
+        writeln!(writer, "entry-delay {}", ruleset.entry_delay_ms)?;
+        writeln!(writer, "line-clear-delay {}", ruleset.line_clear_delay_ms)?;
+        writeln!(writer, "das {}", ruleset.handling.das_ms)?;
+        writeln!(writer, "arr {}", ruleset.handling.arr_ms)?;
+        writeln!(writer, "sdf {}", ruleset.handling.soft_drop_factor)?;
+        writeln!(writer, "scoring {}", ruleset.scoring.name())?;
+        writeln!(writer, "start-level {}", ruleset.start_level)?;
+        writeln!(writer, "lines-per-level {}", ruleset.lines_per_level)?;
+        writeln!(writer, "gravity {}", ruleset.gravity.name())?;
+        writeln!(writer, "board {} {}", ruleset.board_width, ruleset.board_height)?;
+        writeln!(writer, "hidden-rows {}", ruleset.hidden_rows)?;
+        writeln!(writer, "preview {}", ruleset.preview_count)?;
+        writeln!(writer, "hold {}", ruleset.hold_mode.name())?;
+        writeln!(writer, "hold-reset {}", switch_name(ruleset.hold_reset))?;
+        writeln!(writer, "spawn {}", ruleset.spawn_position.name())?;
+        writeln!(writer, "initial-rotation {}", switch_name(ruleset.initial_rotation))?;
+        writeln!(writer, "initial-hold {}", switch_name(ruleset.initial_hold))?;
+        let settings: String = self.mode.settings().iter().map(|value| format!(" {}", value)).collect();
+        writeln!(writer, "mode {}{}", self.mode.name(), settings)?;
+        writeln!(writer, "events")?;
+        for event in &self.events {
+            let (kind, input) = match event.action {
+                ReplayAction::Press(input) => ("press", input),
+                ReplayAction::Release(input) => ("release", input)
+            };
+            writeln!(writer, "{} {} {}", event.time_ms, kind, input.name())?;
+        }
+        writeln!(writer, "end {}", self.end_ms)?;
+        if let Some(result) = self.result {
+            writeln!(writer, "result {} {}", result.score, result.lines_cleared_count)?;
+        }
+        Ok(())
+    }
+
+    /// Read a replay in the text format described in the README.
+    pub fn read_from(reader: impl BufRead) -> Result<Replay, ReplayError> {
+        let mut replay = Replay::new(0, Ruleset::default(), GameMode::Marathon);
+        let mut has_version = false;
+        let mut in_events = false;
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let number = index + 1;
+            let error = |message: &str| ReplayError::Parse { line: number, message: message.to_string() };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some((&key, values)) = fields.split_first() else {
+                continue;
+            };
+
+            if !has_version {
+                if key != "tetris-replay" {
+                    return Err(error("not a tetris replay"));
+                }
+                let version = parse(values, 0).ok_or_else(|| error("missing version"))?;
+                if version == 0 || version > REPLAY_VERSION {
+                    return Err(ReplayError::UnsupportedVersion(version));
+                }
+                if version < 4 {
+                    replay.ruleset.hidden_rows = 0;
+                }
+                if version < 5 {
+                    replay.ruleset.preview_count = 1;
+                }
+                if version < 6 {
+                    replay.ruleset.hold_mode = HoldMode::OncePerPiece;
+                    replay.ruleset.hold_reset = false;
+                }
+                if version < 7 {
+                    replay.ruleset.spawn_position = SpawnPosition::Legacy;
+                    replay.ruleset.initial_rotation = false;
+                    replay.ruleset.initial_hold = false;
+                }
+                if version < 8 {
+                    replay.ruleset.entry_delay_ms = 0;
+                    replay.ruleset.line_clear_delay_ms = 0;
+                }
+                has_version = true;
+                continue;
+            }
+
+            match key {
+                "events" => in_events = true,
+                "end" => replay.end_ms = parse(values, 0).ok_or_else(|| error("bad end time"))?,
+                "result" => {
+                    replay.result = Some(ReplayResult {
+                        score: parse(values, 0).ok_or_else(|| error("bad result score"))?,
+                        lines_cleared_count: parse(values, 1).ok_or_else(|| error("bad result lines"))?
+                    });
+                }
+                _ if in_events => {
+                    let time_ms = key.parse().map_err(|_| error("bad event time"))?;
+                    let input = values.get(1).and_then(|name| GameInput::from_name(name)).ok_or_else(|| error("unknown input"))?;
+                    let action = match values.first() {
+                        Some(&"press") => ReplayAction::Press(input),
+                        Some(&"release") => ReplayAction::Release(input),
+                        _ => return Err(error("expected press or release"))
+                    };
+                    replay.record(time_ms, action);
+                }
+                _ => read_header_field(&mut replay, key, values).ok_or_else(|| error(&format!("bad value for {}", key)))?
+            }
+        }
+        Ok(replay)
+    }
+}
+
+/// Parse the value at `index` of a line's values.
+fn parse<T: std::str::FromStr>(values: &[&str], index: usize) -> Option<T> {
+    values.get(index)?.parse().ok()
+}
+
+/// `on` or `off`, as switches are written in the header.
+fn switch_name(on: bool) -> &'static str {
+    if on { "on" } else { "off" }
+}
+
+/// Parse a name produced by `switch_name`.
+fn switch_from_name(name: &str) -> Option<bool> {
+    match name {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None
+    }
+}
+
+/// Apply one `key value` line from the header. Returns `None` if the value is bad.
+fn read_header_field(replay: &mut Replay, key: &str, values: &[&str]) -> Option<()> {
+    let ruleset = &mut replay.ruleset;
+    let name = values.first().copied().unwrap_or("");
+    match key {
+        "seed" => replay.seed = parse(values, 0)?,
+        "randomizer" => ruleset.randomizer = RandomizerKind::from_name(name)?,
+        "rotation" => ruleset.rotation_system = RotationSystemKind::from_name(name)?,
+        "lock-delay" => ruleset.lock_delay_ms = parse(values, 0)?,
+        "lock-mode" => {
+            ruleset.lock_delay_mode = match LockDelayMode::from_name(name)? {
+                LockDelayMode::MoveReset { limit } => LockDelayMode::MoveReset { limit: parse(values, 1).unwrap_or(limit) },
+                mode => mode
+            };
+        }
+        "entry-delay" => ruleset.entry_delay_ms = parse(values, 0)?,
+        "line-clear-delay" => ruleset.line_clear_delay_ms = parse(values, 0)?,
+        "das" => ruleset.handling.das_ms = parse(values, 0)?,
+        "arr" => ruleset.handling.arr_ms = parse(values, 0)?,
+        "sdf" => ruleset.handling.soft_drop_factor = parse(values, 0)?,
+        "scoring" => ruleset.scoring = ScoringKind::from_name(name)?,
+        "start-level" => ruleset.start_level = parse(values, 0)?,
+        "lines-per-level" => ruleset.lines_per_level = parse(values, 0)?,
+        "gravity" => ruleset.gravity = GravityCurve::from_name(name)?,
+        "board" => {
+            ruleset.board_width = parse(values, 0)?;
+            ruleset.board_height = parse(values, 1)?;
+        }
+        "hidden-rows" => ruleset.hidden_rows = parse(values, 0)?,
+        "preview" => ruleset.preview_count = parse(values, 0)?,
+        "hold" => ruleset.hold_mode = HoldMode::from_name(name)?,
+        "hold-reset" => ruleset.hold_reset = switch_from_name(name)?,
+        "spawn" => ruleset.spawn_position = SpawnPosition::from_name(name)?,
+        "initial-rotation" => ruleset.initial_rotation = switch_from_name(name)?,
+        "initial-hold" => ruleset.initial_hold = switch_from_name(name)?,
+        "mode" => {
+            let mode = GameMode::from_name(name)?;
+            let settings = values[1..].iter().map(|value| value.parse().ok()).collect::<Option<Vec<u32>>>()?;
+            replay.mode = mode.with_settings(&settings);
+        }
+        _ => return None
+    }
+    Some(())
+}
+
+/// Feeds a replay's inputs back into a game at the times they were recorded.
+#[derive(Clone, Debug)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    /// Index of the next event to apply.
+    next_event: usize,
+    /// Time handed to `step` that has not yet added up to a full tick.
+    unsimulated: Duration
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer { replay, next_event: 0, unsimulated: Duration::ZERO }
+    }
+
+    /// The replay being played.
+    pub fn replay(&self) -> &Replay { &self.replay }
+
+    /// Build and start the game the replay was recorded from.
+    pub fn start(&mut self) -> GameState {
+        self.next_event = 0;
+        self.unsimulated = Duration::ZERO;
+        let mut game = GameState::with_mode(self.replay.ruleset, self.replay.mode, self.replay.seed);
+        game.start(self.replay.seed);
+        game
+    }
+
+    /// Has the replay reached the point the recording stopped at.
+    pub fn is_finished(&self, game: &GameState) -> bool {
+        !game.is_playing() || (self.next_event >= self.replay.events.len() && game.elapsed_ms() >= self.replay.end_ms)
+    }
+
+    /// Advance the game by `dt` in real time, applying recorded inputs as their time comes.
+    pub fn step(&mut self, game: &mut GameState, dt: Duration) {
+        self.unsimulated += dt;
+        while self.unsimulated >= TICK && !self.is_finished(game) {
+            self.unsimulated -= TICK;
+            self.tick(game);
+        }
+    }
+
+    /// Apply every input recorded for the game's current time, then run one tick.
+    pub fn tick(&mut self, game: &mut GameState) {
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.time_ms > game.elapsed_ms() {
+                break;
+            }
+            match event.action {
+                ReplayAction::Press(input) => { game.press(input); }
+                ReplayAction::Release(input) => game.release(input)
+            }
+            self.next_event += 1;
+        }
+        if game.elapsed_ms() < self.replay.end_ms || self.next_event < self.replay.events.len() {
+            game.tick();
+        }
+    }
+}
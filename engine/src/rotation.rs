@@ -0,0 +1,241 @@
+use std::fmt::Debug;
+
+use crate::board::Board;
+use crate::piece::{mask_cells, Piece, PieceKind};
+
+/// Rules for the shape of every piece in each rotation state and for how a
+/// blocked rotation is nudged ("kicked") into a free spot.
+pub trait RotationSystem: Debug {
+    /// Rotation masks of the piece, one 4x4 grid per rotation state, clockwise from the spawn state.
+    fn shapes(&self, kind: PieceKind) -> [u32; 4];
+
+    /// Column the leftmost block of the piece enters at on a standard 10 column board,
+    /// in the spawn state `shapes` starts from.
+    fn spawn_column(&self, kind: PieceKind) -> i8;
+
+    /// Try to turn `piece` a quarter turn on `board`.
+    /// Returns the rotated piece and the index of the kick that made room for it,
+    /// where `0` is the unkicked rotation, or `None` if every attempt collided.
+    fn rotate(&self, board: &Board, piece: &Piece, clockwise: bool) -> Option<(Piece, usize)>;
+}
+
+/// Selectable rotation systems.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RotationSystemKind {
+    /// Guideline Super Rotation System with wall and floor kicks.
+    #[default]
+    Srs,
+    /// Arika Rotation System used by TGM, with single column kicks.
+    Ars,
+    /// NES style rotation that never kicks.
+    Classic
+}
+
+impl RotationSystemKind {
+    /// Every rotation system, in declaration order.
+    pub const ALL: [RotationSystemKind; 3] = [RotationSystemKind::Srs, RotationSystemKind::Ars, RotationSystemKind::Classic];
+
+    /// The rotation system implementing this kind.
+    pub fn system(self) -> &'static dyn RotationSystem {
+        match self {
+            RotationSystemKind::Srs => &Srs,
+            RotationSystemKind::Ars => &Ars,
+            RotationSystemKind::Classic => &Classic
+        }
+    }
+
+    /// Short stable name of the rotation system.
+    pub fn name(self) -> &'static str {
+        match self {
+            RotationSystemKind::Srs => "srs",
+            RotationSystemKind::Ars => "ars",
+            RotationSystemKind::Classic => "classic"
+        }
+    }
+
+    /// Parse a name produced by `RotationSystemKind::name`.
+    pub fn from_name(name: &str) -> Option<RotationSystemKind> {
+        RotationSystemKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// Rotation state reached by turning a quarter turn from `rotation_state`.
+fn next_state(rotation_state: i8, clockwise: bool) -> i8 {
+    if clockwise { (rotation_state + 1) % 4 } else { (rotation_state + 3) % 4 }
+}
+
+/// Try each offset in order and return the first rotated piece that fits.
+fn first_fit(board: &Board, piece: &Piece, rotation_state: i8, offsets: &[(i8, i8)]) -> Option<(Piece, usize)> {
+    let mask = piece.rotation[rotation_state as usize];
+    offsets.iter().enumerate().find_map(|(index, &(dx, dy))| {
+        if board.collides(mask, piece.x + dx, piece.y + dy) {
+            return None;
+        }
+        let mut rotated = *piece;
+        rotated.rotation_state = rotation_state;
+        rotated.x += dx;
+        rotated.y += dy;
+        Some((rotated, index))
+    })
+}
+
+/// Super Rotation System.
+#[derive(Debug)]
+pub struct Srs;
+
+/// SRS kicks for J, L, S, T and Z, indexed by the starting rotation state and then
+/// clockwise/counter-clockwise. Offsets use the guideline convention of y pointing up.
+const SRS_JLSTZ_KICKS: [[[(i8, i8); 5]; 2]; 4] = [
+    [[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]],
+    [[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)], [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]],
+    [[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)], [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]],
+    [[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)], [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]]
+];
+
+/// SRS kicks for I, laid out like `SRS_JLSTZ_KICKS`.
+const SRS_I_KICKS: [[[(i8, i8); 5]; 2]; 4] = [
+    [[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]],
+    [[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]],
+    [[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]],
+    [[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]]
+];
+
+impl RotationSystem for Srs {
+    fn shapes(&self, kind: PieceKind) -> [u32; 4] {
+        match kind {
+            PieceKind::I => [0x0F00, 0x2222, 0x00F0, 0x4444],
+            PieceKind::J => [0x8E00, 0x6440, 0x0E20, 0x44C0],
+            PieceKind::L => [0x2E00, 0x4460, 0x0E80, 0xC440],
+            PieceKind::O => [0x6600, 0x6600, 0x6600, 0x6600],
+            PieceKind::S => [0x6C00, 0x4620, 0x06C0, 0x8C40],
+            PieceKind::T => [0x4E00, 0x4640, 0x0E40, 0x4C40],
+            PieceKind::Z => [0xC600, 0x2640, 0x0C60, 0x4C80]
+        }
+    }
+
+    fn spawn_column(&self, kind: PieceKind) -> i8 {
+        // Pieces enter left of centre when they can't be centred.
+        match kind {
+            PieceKind::O => 4,
+            _ => 3
+        }
+    }
+
+    fn rotate(&self, board: &Board, piece: &Piece, clockwise: bool) -> Option<(Piece, usize)> {
+        let rotation_state = next_state(piece.rotation_state, clockwise);
+        let table = match piece.kind {
+            PieceKind::O => return first_fit(board, piece, rotation_state, &[(0, 0)]),
+            PieceKind::I => &SRS_I_KICKS,
+            _ => &SRS_JLSTZ_KICKS
+        };
+        let direction = if clockwise { 0 } else { 1 };
+        // The board's y axis points down.
+        let offsets = table[piece.rotation_state as usize][direction].map(|(dx, dy)| (dx, -dy));
+        first_fit(board, piece, rotation_state, &offsets)
+    }
+}
+
+/// Arika Rotation System.
+#[derive(Debug)]
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn shapes(&self, kind: PieceKind) -> [u32; 4] {
+        match kind {
+            PieceKind::I => [0x0F00, 0x2222, 0x0F00, 0x2222],
+            PieceKind::J => [0x0E20, 0x44C0, 0x08E0, 0x6440],
+            PieceKind::L => [0x0E80, 0xC440, 0x02E0, 0x4460],
+            PieceKind::O => [0x0660, 0x0660, 0x0660, 0x0660],
+            PieceKind::S => [0x06C0, 0x8C40, 0x06C0, 0x8C40],
+            PieceKind::T => [0x0E40, 0x4C40, 0x04E0, 0x4640],
+            PieceKind::Z => [0x0C60, 0x2640, 0x0C60, 0x2640]
+        }
+    }
+
+    fn spawn_column(&self, kind: PieceKind) -> i8 {
+        match kind {
+            PieceKind::O => 4,
+            _ => 3
+        }
+    }
+
+    fn rotate(&self, board: &Board, piece: &Piece, clockwise: bool) -> Option<(Piece, usize)> {
+        let rotation_state = next_state(piece.rotation_state, clockwise);
+        if piece.kind == PieceKind::I {
+            return first_fit(board, piece, rotation_state, &[(0, 0)]);
+        }
+        if matches!(piece.kind, PieceKind::J | PieceKind::L | PieceKind::T) {
+            // Centre column rule: when the first blocked cell of the rotated piece, read
+            // left to right and top to bottom, is in the middle column, there is no kick.
+            let mask = piece.rotation[rotation_state as usize];
+            let first_blocked = mask_cells(mask).find(|&(dx, dy)| board.is_solid(piece.x + dx, piece.y + dy));
+            if let Some((1, _)) = first_blocked {
+                return None;
+            }
+        }
+        first_fit(board, piece, rotation_state, &[(0, 0), (1, 0), (-1, 0)])
+    }
+}
+
+/// NES style rotation.
+#[derive(Debug)]
+pub struct Classic;
+
+impl RotationSystem for Classic {
+    fn shapes(&self, kind: PieceKind) -> [u32; 4] {
+        match kind {
+            PieceKind::I => [0x00F0, 0x2222, 0x00F0, 0x2222],
+            PieceKind::J => [0x0E20, 0x44C0, 0x8E00, 0x6440],
+            PieceKind::L => [0x0E80, 0xC440, 0x2E00, 0x4460],
+            PieceKind::O => [0x0660, 0x0660, 0x0660, 0x0660],
+            PieceKind::S => [0x06C0, 0x4620, 0x06C0, 0x4620],
+            PieceKind::T => [0x0E40, 0x4C40, 0x4E00, 0x4640],
+            PieceKind::Z => [0x0C60, 0x2640, 0x0C60, 0x2640]
+        }
+    }
+
+    fn spawn_column(&self, kind: PieceKind) -> i8 {
+        // The NES centres three wide pieces one column right of the guideline.
+        match kind {
+            PieceKind::I => 3,
+            _ => 4
+        }
+    }
+
+    fn rotate(&self, board: &Board, piece: &Piece, clockwise: bool) -> Option<(Piece, usize)> {
+        first_fit(board, piece, next_state(piece.rotation_state, clockwise), &[(0, 0)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An SRS piece of the given kind in the given rotation state and position.
+    fn srs_piece(kind: PieceKind, rotation_state: i8, x: i8, y: i8) -> Piece {
+        Piece { rotation_state, x, y, ..Piece::new(kind, Srs.shapes(kind)) }
+    }
+
+    #[test]
+    fn srs_kicks_i_and_t_off_each_wall() {
+        let board = Board::new(10, 20, 0);
+        // Piece, rotation state and column pressed against a wall, the direction turned, then
+        // the column, rotation state and kick index the piece should end up with.
+        let cases = [
+            (PieceKind::T, 1, -1, true, 0, 2, 1),
+            (PieceKind::T, 1, -1, false, 0, 0, 1),
+            (PieceKind::T, 3, 8, true, 7, 0, 1),
+            (PieceKind::T, 3, 8, false, 7, 2, 1),
+            (PieceKind::I, 1, -2, true, 0, 2, 2),
+            (PieceKind::I, 1, -2, false, 0, 0, 1),
+            (PieceKind::I, 3, 8, true, 6, 0, 2),
+            (PieceKind::I, 3, 8, false, 6, 2, 1)
+        ];
+        for (kind, rotation_state, x, clockwise, kicked_x, kicked_state, kick) in cases {
+            let piece = srs_piece(kind, rotation_state, x, 10);
+            assert!(!board.collides(piece.get_rotation_state(), piece.x, piece.y));
+            let expected = srs_piece(kind, kicked_state, kicked_x, 10);
+            assert_eq!(Srs.rotate(&board, &piece, clockwise), Some((expected, kick)), "{:?} from state {} at x {}", kind, rotation_state, x);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use std::ops::RangeInclusive;
+
+use crate::board::{BOARD_HEIGHT, BOARD_HEIGHT_RANGE, BOARD_WIDTH, BOARD_WIDTH_RANGE, HIDDEN_ROWS_RANGE};
+use crate::gravity::GravityCurve;
+use crate::randomizer::RandomizerKind;
+use crate::rotation::RotationSystemKind;
+use crate::scoring::ScoringKind;
+
+/// Most upcoming pieces a ruleset can show.
+pub const MAX_PREVIEW_COUNT: usize = 6;
+
+/// Levels a game can start from.
+pub const START_LEVEL_RANGE: RangeInclusive<u32> = 1..=99;
+
+/// What gives a piece resting on the stack more time before it locks.
+/// Reaching a new lowest row always resets the lock delay.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LockDelayMode {
+    /// Every shift or rotation on the ground resets the lock delay.
+    Infinity,
+    /// Shifts and rotations on the ground reset the lock delay up to `limit` times per
+    /// row, after which the piece locks as soon as it touches the ground.
+    MoveReset { limit: u32 },
+    /// Only moving down a row resets the lock delay.
+    StepReset,
+    /// No lock delay: the piece locks when gravity next fails to pull it down, so it can
+    /// still slide and turn for one gravity interval, as on the NES. `lock_delay_ms` is ignored.
+    Gravity
+}
+
+impl LockDelayMode {
+    /// Short stable name of the mode.
+    pub fn name(self) -> &'static str {
+        match self {
+            LockDelayMode::Infinity => "infinity",
+            LockDelayMode::MoveReset { .. } => "move",
+            LockDelayMode::StepReset => "step",
+            LockDelayMode::Gravity => "gravity"
+        }
+    }
+
+    /// Parse a name produced by `LockDelayMode::name`. Move reset gets the guideline limit of 15.
+    pub fn from_name(name: &str) -> Option<LockDelayMode> {
+        match name {
+            "infinity" => Some(LockDelayMode::Infinity),
+            "move" => Some(LockDelayMode::MoveReset { limit: 15 }),
+            "step" => Some(LockDelayMode::StepReset),
+            "gravity" => Some(LockDelayMode::Gravity),
+            _ => None
+        }
+    }
+}
+
+/// When the current piece may be swapped into the hold box.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HoldMode {
+    /// There is no hold box.
+    Disabled,
+    /// One swap per piece, allowed again once the piece locks.
+    #[default]
+    OncePerPiece,
+    /// Any number of swaps.
+    Unlimited
+}
+
+impl HoldMode {
+    /// Every hold mode, in declaration order.
+    pub const ALL: [HoldMode; 3] = [HoldMode::Disabled, HoldMode::OncePerPiece, HoldMode::Unlimited];
+
+    /// Short stable name of the mode.
+    pub fn name(self) -> &'static str {
+        match self {
+            HoldMode::Disabled => "off",
+            HoldMode::OncePerPiece => "once",
+            HoldMode::Unlimited => "unlimited"
+        }
+    }
+
+    /// Parse a name produced by `HoldMode::name`.
+    pub fn from_name(name: &str) -> Option<HoldMode> {
+        HoldMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// Where new pieces enter the board.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SpawnPosition {
+    /// Each piece enters at the column its rotation system gives it.
+    #[default]
+    Standard,
+    /// Every piece's 4x4 box enters at the same column right of the middle, as replays
+    /// before version 7 were recorded with.
+    Legacy
+}
+
+impl SpawnPosition {
+    /// Every spawn position, in declaration order.
+    pub const ALL: [SpawnPosition; 2] = [SpawnPosition::Standard, SpawnPosition::Legacy];
+
+    /// Short stable name of the spawn position.
+    pub fn name(self) -> &'static str {
+        match self {
+            SpawnPosition::Standard => "standard",
+            SpawnPosition::Legacy => "legacy"
+        }
+    }
+
+    /// Parse a name produced by `SpawnPosition::name`.
+    pub fn from_name(name: &str) -> Option<SpawnPosition> {
+        SpawnPosition::ALL.into_iter().find(|position| position.name() == name)
+    }
+}
+
+/// Timings for held movement keys.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Handling {
+    /// Delayed auto shift: how long left or right is held before it starts repeating, in milliseconds.
+    pub das_ms: u64,
+    /// Auto repeat rate: milliseconds between repeated shifts once DAS has charged.
+    /// `0` moves the piece straight to the wall.
+    pub arr_ms: u64,
+    /// How many times faster than gravity a held soft drop falls.
+    pub soft_drop_factor: u64
+}
+
+/// Settings that decide how a game plays.
+/// Two games with the same ruleset and seed play out identically for the same inputs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ruleset {
+    /// Strategy the piece sequence is drawn with.
+    pub randomizer: RandomizerKind,
+    /// Piece shapes and kicks used when rotating.
+    pub rotation_system: RotationSystemKind,
+    /// How long a piece may rest on the stack before it locks, in milliseconds.
+    pub lock_delay_ms: u64,
+    /// What resets the lock delay.
+    pub lock_delay_mode: LockDelayMode,
+    /// Entry delay (ARE): how long the next piece waits before it enters, in milliseconds.
+    pub entry_delay_ms: u64,
+    /// How long completed lines stay on the board before they are removed, in milliseconds.
+    pub line_clear_delay_ms: u64,
+    /// Timings for held movement keys.
+    pub handling: Handling,
+    /// How many points clears and drops are worth.
+    pub scoring: ScoringKind,
+    /// Level the game starts at. Levels count from 1.
+    pub start_level: u32,
+    /// Lines to clear for every level gained, or `0` to stay at the start level.
+    pub lines_per_level: u32,
+    /// How fast pieces fall at each level.
+    pub gravity: GravityCurve,
+    /// Number of columns on the board.
+    pub board_width: usize,
+    /// Number of visible rows on the board.
+    pub board_height: usize,
+    /// Number of hidden rows above the visible ones, where pieces enter and can still move.
+    pub hidden_rows: usize,
+    /// Number of upcoming pieces shown, up to `MAX_PREVIEW_COUNT`.
+    pub preview_count: usize,
+    /// When the current piece may be swapped into the hold box.
+    pub hold_mode: HoldMode,
+    /// Does a piece swapped into or out of the hold box go back to its spawn position and
+    /// orientation. Without it a held piece comes back where it was held and is not checked
+    /// against the stack, as replays before version 6 were recorded with.
+    pub hold_reset: bool,
+    /// Where new pieces enter the board.
+    pub spawn_position: SpawnPosition,
+    /// Initial rotation system: a rotation held as a piece enters turns it straight away.
+    pub initial_rotation: bool,
+    /// Initial hold system: hold held as a piece enters swaps it straight into the hold box.
+    pub initial_hold: bool
+}
+
+impl Ruleset {
+    /// Names of every preset, as accepted by `Ruleset::from_name`.
+    pub const PRESETS: [&'static str; 3] = ["guideline", "classic", "tgm"];
+
+    /// Modern guideline rules.
+    pub fn guideline() -> Self {
+        Ruleset {
+            randomizer: RandomizerKind::SevenBag,
+            rotation_system: RotationSystemKind::Srs,
+            lock_delay_ms: 500,
+            lock_delay_mode: LockDelayMode::MoveReset { limit: 15 },
+            entry_delay_ms: 0,
+            line_clear_delay_ms: 0,
+            handling: Handling { das_ms: 167, arr_ms: 33, soft_drop_factor: 20 },
+            scoring: ScoringKind::Guideline,
+            start_level: 1,
+            lines_per_level: 10,
+            gravity: GravityCurve::Guideline,
+            board_width: BOARD_WIDTH,
+            board_height: BOARD_HEIGHT,
+            hidden_rows: 20,
+            preview_count: 5,
+            hold_mode: HoldMode::OncePerPiece,
+            hold_reset: true,
+            spawn_position: SpawnPosition::Standard,
+            initial_rotation: false,
+            initial_hold: false
+        }
+    }
+
+    /// NES style rules.
+    pub fn classic() -> Self {
+        Ruleset {
+            randomizer: RandomizerKind::Uniform,
+            rotation_system: RotationSystemKind::Classic,
+            lock_delay_ms: 0,
+            lock_delay_mode: LockDelayMode::Gravity,
+            entry_delay_ms: 167,
+            line_clear_delay_ms: 333,
+            handling: Handling { das_ms: 267, arr_ms: 100, soft_drop_factor: 20 },
+            scoring: ScoringKind::Classic,
+            start_level: 1,
+            lines_per_level: 10,
+            gravity: GravityCurve::Classic,
+            board_width: BOARD_WIDTH,
+            board_height: BOARD_HEIGHT,
+            hidden_rows: 0,
+            preview_count: 1,
+            hold_mode: HoldMode::Disabled,
+            hold_reset: true,
+            spawn_position: SpawnPosition::Standard,
+            initial_rotation: false,
+            initial_hold: false
+        }
+    }
+
+    /// TGM style rules.
+    pub fn tgm() -> Self {
+        Ruleset {
+            randomizer: RandomizerKind::History,
+            rotation_system: RotationSystemKind::Ars,
+            lock_delay_ms: 500,
+            lock_delay_mode: LockDelayMode::StepReset,
+            entry_delay_ms: 500,
+            line_clear_delay_ms: 683,
+            handling: Handling { das_ms: 267, arr_ms: 17, soft_drop_factor: 20 },
+            scoring: ScoringKind::Guideline,
+            start_level: 1,
+            lines_per_level: 10,
+            gravity: GravityCurve::TwentyG,
+            board_width: BOARD_WIDTH,
+            board_height: BOARD_HEIGHT,
+            hidden_rows: 0,
+            preview_count: 1,
+            hold_mode: HoldMode::OncePerPiece,
+            hold_reset: true,
+            spawn_position: SpawnPosition::Standard,
+            initial_rotation: true,
+            initial_hold: true
+        }
+    }
+
+    /// Look up one of the presets above by name.
+    pub fn from_name(name: &str) -> Option<Ruleset> {
+        match name {
+            "guideline" => Some(Ruleset::guideline()),
+            "classic" => Some(Ruleset::classic()),
+            "tgm" => Some(Ruleset::tgm()),
+            _ => None
+        }
+    }
+
+    /// Name of the preset these rules match, or `None` if any setting was changed.
+    /// The board size is not part of any preset, so it is left out of the comparison.
+    pub fn preset_name(&self) -> Option<&'static str> {
+        Ruleset::PRESETS.into_iter().find(|&name| {
+            Ruleset::from_name(name).is_some_and(|preset| Ruleset { board_width: self.board_width, board_height: self.board_height, ..preset } == *self)
+        })
+    }
+
+    /// These rules with every setting pulled into the range the engine supports, so the board
+    /// size and level recorded in replays and high score tables are the ones actually played.
+    pub fn normalized(self) -> Self {
+        Ruleset {
+            board_width: self.board_width.clamp(*BOARD_WIDTH_RANGE.start(), *BOARD_WIDTH_RANGE.end()),
+            board_height: self.board_height.clamp(*BOARD_HEIGHT_RANGE.start(), *BOARD_HEIGHT_RANGE.end()),
+            hidden_rows: self.hidden_rows.clamp(*HIDDEN_ROWS_RANGE.start(), *HIDDEN_ROWS_RANGE.end()),
+            start_level: self.start_level.clamp(*START_LEVEL_RANGE.start(), *START_LEVEL_RANGE.end()),
+            ..self
+        }
+    }
+
+    /// Apply a command line flag the frontends share, such as `--ruleset tgm` or `--das 100`,
+    /// and return whether `flag` is one of them. `--ruleset` replaces every setting with the
+    /// preset, so it should come before the flags that tune it. Values that don't parse
+    /// leave the setting as it was, and `normalized` pulls the rest into range afterwards.
+    pub fn apply_arg(&mut self, flag: &str, value: &str) -> bool {
+        match flag {
+            "--ruleset" => *self = Ruleset::from_name(value).unwrap_or(*self),
+            "--width" => self.board_width = value.parse().unwrap_or(self.board_width),
+            "--height" => self.board_height = value.parse().unwrap_or(self.board_height),
+            "--hidden-rows" => self.hidden_rows = value.parse().unwrap_or(self.hidden_rows),
+            "--preview" => self.preview_count = value.parse().unwrap_or(self.preview_count),
+            "--hold" => self.hold_mode = HoldMode::from_name(value).unwrap_or(self.hold_mode),
+            "--hold-reset" => self.hold_reset = value == "on",
+            "--initial-rotation" => self.initial_rotation = value == "on",
+            "--initial-hold" => self.initial_hold = value == "on",
+            "--randomizer" => self.randomizer = RandomizerKind::from_name(value).unwrap_or(self.randomizer),
+            "--rotation" => self.rotation_system = RotationSystemKind::from_name(value).unwrap_or(self.rotation_system),
+            "--lock-delay" => self.lock_delay_ms = value.parse().unwrap_or(self.lock_delay_ms),
+            "--lock-mode" => self.lock_delay_mode = LockDelayMode::from_name(value).unwrap_or(self.lock_delay_mode),
+            "--entry-delay" => self.entry_delay_ms = value.parse().unwrap_or(self.entry_delay_ms),
+            "--line-clear-delay" => self.line_clear_delay_ms = value.parse().unwrap_or(self.line_clear_delay_ms),
+            "--das" => self.handling.das_ms = value.parse().unwrap_or(self.handling.das_ms),
+            "--arr" => self.handling.arr_ms = value.parse().unwrap_or(self.handling.arr_ms),
+            "--sdf" => self.handling.soft_drop_factor = value.parse().unwrap_or(self.handling.soft_drop_factor),
+            "--scoring" => self.scoring = ScoringKind::from_name(value).unwrap_or(self.scoring),
+            "--level" => self.start_level = value.parse().unwrap_or(self.start_level),
+            "--gravity" => self.gravity = GravityCurve::from_name(value).unwrap_or(self.gravity),
+            _ => return false
+        }
+        true
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset::guideline()
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt::Debug;
+
+use crate::board::Board;
+use crate::piece::{mask_cells, Piece, PieceKind};
+
+/// Kind of T-spin a locked piece scored.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TSpin {
+    /// Not a T-spin.
+    #[default]
+    None,
+    /// T-spin with only one of the corners the T points at filled.
+    Mini,
+    /// T-spin with both corners the T points at filled.
+    Full
+}
+
+/// Everything the scoring rule needs to know about a locked piece.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LineClear {
+    /// Number of rows cleared. `0` for a T-spin that cleared nothing.
+    pub lines: u32,
+    /// Kind of T-spin the piece was locked with.
+    pub t_spin: TSpin,
+    /// Consecutive clears before this one, `0` for the first clear of a combo.
+    pub combo: u32,
+    /// Is this a tetris or T-spin clear that directly follows another one.
+    pub back_to_back: bool,
+    /// Did the clear leave the board empty.
+    pub perfect_clear: bool,
+    /// Level the clear was made at.
+    pub level: u32
+}
+
+impl LineClear {
+    /// Tetrises and T-spin clears are "difficult" and keep a back-to-back chain going.
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.lines > 0 && self.t_spin != TSpin::None)
+    }
+}
+
+/// Rules for how many points clears and drops are worth.
+pub trait ScoringRule: Debug {
+    /// Points for a locked piece that cleared lines or scored a T-spin.
+    fn line_clear(&self, clear: &LineClear) -> i32;
+
+    /// Points for soft dropping `rows` rows.
+    fn soft_drop(&self, rows: u32) -> i32;
+
+    /// Points for hard dropping `rows` rows.
+    fn hard_drop(&self, rows: u32) -> i32;
+}
+
+/// Selectable scoring rules.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ScoringKind {
+    /// Guideline scoring with T-spins, back-to-back, combos and perfect clears.
+    #[default]
+    Guideline,
+    /// NES scoring.
+    Classic
+}
+
+impl ScoringKind {
+    /// Every scoring rule, in declaration order.
+    pub const ALL: [ScoringKind; 2] = [ScoringKind::Guideline, ScoringKind::Classic];
+
+    /// The scoring rule implementing this kind.
+    pub fn rule(self) -> &'static dyn ScoringRule {
+        match self {
+            ScoringKind::Guideline => &GuidelineScoring,
+            ScoringKind::Classic => &ClassicScoring
+        }
+    }
+
+    /// Short stable name of the scoring rule.
+    pub fn name(self) -> &'static str {
+        match self {
+            ScoringKind::Guideline => "guideline",
+            ScoringKind::Classic => "classic"
+        }
+    }
+
+    /// Parse a name produced by `ScoringKind::name`.
+    pub fn from_name(name: &str) -> Option<ScoringKind> {
+        ScoringKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// Guideline scoring.
+#[derive(Debug)]
+pub struct GuidelineScoring;
+
+impl ScoringRule for GuidelineScoring {
+    fn line_clear(&self, clear: &LineClear) -> i32 {
+        // Long games reach levels and combos big enough to overflow, so points stop at `i32::MAX`.
+        let level = i32::try_from(clear.level).unwrap_or(i32::MAX);
+        let base: i32 = match (clear.t_spin, clear.lines) {
+            (TSpin::None, 0) => 0,
+            (TSpin::None, 1) => 100,
+            (TSpin::None, 2) => 300,
+            (TSpin::None, 3) => 500,
+            (TSpin::None, _) => 800,
+            (TSpin::Mini, 0) => 100,
+            (TSpin::Mini, 1) => 200,
+            (TSpin::Mini, _) => 400,
+            (TSpin::Full, 0) => 400,
+            (TSpin::Full, 1) => 800,
+            (TSpin::Full, 2) => 1200,
+            (TSpin::Full, _) => 1600
+        };
+        let mut points = base.saturating_mul(level);
+        if clear.back_to_back {
+            points = points.saturating_add(points / 2);
+        }
+        if clear.lines > 0 {
+            let combo = i32::try_from(clear.combo).unwrap_or(i32::MAX);
+            points = points.saturating_add(combo.saturating_mul(50).saturating_mul(level));
+        }
+        if clear.perfect_clear {
+            points = points.saturating_add(level.saturating_mul(match clear.lines {
+                1 => 800,
+                2 => 1200,
+                3 => 1800,
+                _ if clear.back_to_back => 3200,
+                _ => 2000
+            }));
+        }
+        points
+    }
+
+    fn soft_drop(&self, rows: u32) -> i32 { rows as i32 }
+
+    fn hard_drop(&self, rows: u32) -> i32 { 2 * rows as i32 }
+}
+
+/// NES scoring. Levels here count from 1, so they are one more than the NES shows.
+#[derive(Debug)]
+pub struct ClassicScoring;
+
+impl ScoringRule for ClassicScoring {
+    fn line_clear(&self, clear: &LineClear) -> i32 {
+        let base: i32 = match clear.lines {
+            0 => 0,
+            1 => 40,
+            2 => 100,
+            3 => 300,
+            _ => 1200
+        };
+        base.saturating_mul(i32::try_from(clear.level).unwrap_or(i32::MAX))
+    }
+
+    fn soft_drop(&self, rows: u32) -> i32 { rows as i32 }
+
+    fn hard_drop(&self, rows: u32) -> i32 { rows as i32 }
+}
+
+/// Classify a T about to lock with the 3-corner rule. Only counts when the last thing
+/// that moved the piece was a rotation, whose kick index is `last_kick`.
+pub fn detect_t_spin(board: &Board, piece: &Piece, last_kick: Option<usize>) -> TSpin {
+    if piece.kind != PieceKind::T {
+        return TSpin::None;
+    }
+    let Some(kick) = last_kick else {
+        return TSpin::None;
+    };
+
+    let blocks: Vec<(i8, i8)> = mask_cells(piece.get_rotation_state()).collect();
+    let has = |x: i8, y: i8| blocks.contains(&(x, y));
+    // The centre is the only block with three neighbours; the nub is the neighbour without an opposite.
+    let Some(&(cx, cy)) = blocks.iter().find(|&&(x, y)| [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().filter(|&&(dx, dy)| has(x + dx, y + dy)).count() == 3) else {
+        return TSpin::None;
+    };
+    let Some(&(nx, ny)) = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().find(|&&(dx, dy)| has(cx + dx, cy + dy) && !has(cx - dx, cy - dy)) else {
+        return TSpin::None;
+    };
+
+    let filled = |dx: i8, dy: i8| board.is_solid(piece.x + cx + dx, piece.y + cy + dy);
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+    if corners.iter().filter(|&&(dx, dy)| filled(dx, dy)).count() < 3 {
+        return TSpin::None;
+    }
+
+    // The two corners on the side the nub points at.
+    let front = if nx == 0 { [(-1, ny), (1, ny)] } else { [(nx, -1), (nx, 1)] };
+    if front.iter().all(|&(dx, dy)| filled(dx, dy)) || kick == 4 {
+        TSpin::Full
+    }
+    else {
+        TSpin::Mini
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceColor;
+    use crate::rotation::{RotationSystem, Srs};
+
+    /// A board with the given rows, top first, where `#` is a filled cell.
+    fn board(rows: &[&str]) -> Board {
+        let mut board = Board::new(rows[0].len(), rows.len(), 0);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                if cell == '#' {
+                    board.set(x, y, Some(PieceColor::Gray));
+                }
+            }
+        }
+        board
+    }
+
+    /// An SRS T in the given rotation state and position.
+    fn t_piece(rotation_state: i8, x: i8, y: i8) -> Piece {
+        Piece { rotation_state, x, y, ..Piece::new(PieceKind::T, Srs.shapes(PieceKind::T)) }
+    }
+
+    #[test]
+    fn front_corners_decide_mini_or_full() {
+        // A T pointing down at the bottom of a four row board, with its centre at (4, 2). The
+        // corners it points at are on the bottom row and the ones behind it on the row above the centre.
+        let cases = [
+            (["..........", "...#......", "..........", "...#.#...."], Some(0), TSpin::Full),
+            (["..........", "...#.#....", "..........", "...#......"], Some(0), TSpin::Mini),
+            (["..........", "...#.#....", "..........", "...#......"], Some(4), TSpin::Full),
+            (["..........", "..........", "..........", "...#.#...."], Some(0), TSpin::None),
+            (["..........", "...#......", "..........", "...#.#...."], None, TSpin::None)
+        ];
+        for (rows, last_kick, expected) in cases {
+            assert_eq!(detect_t_spin(&board(&rows), &t_piece(2, 3, 1), last_kick), expected, "{:?} after kick {:?}", rows, last_kick);
+        }
+    }
+
+    #[test]
+    fn t_spin_triple_kick_scores_a_full_t_spin() {
+        // Turning left from spawn only fits with the last kick, which drops the T under the overhang at (3, 5).
+        // The second board leaves the lower corner the T points at open, which on its own would be a mini.
+        let cases = [
+            (["####.#####", "###..#####", "####.#####"], 3),
+            (["####.#####", "###..#####", "###..#####"], 2)
+        ];
+        for (stack, lines) in cases {
+            let mut rows = vec![".........."; 5];
+            rows[3] = "....#.....";
+            rows.extend(stack);
+            let mut board = board(&rows);
+            let (piece, kick) = Srs.rotate(&board, &t_piece(0, 2, 3), false).unwrap();
+            assert_eq!((piece.x, piece.y, piece.rotation_state, kick), (3, 5, 3, 4));
+            assert_eq!(detect_t_spin(&board, &piece, Some(kick)), TSpin::Full);
+            board.commit(&piece);
+            assert_eq!(board.full_lines().len(), lines);
+        }
+    }
+
+    #[test]
+    fn t_spin_double_scores_the_guideline_value() {
+        let mut board = board(&["..........", "...#......", "###...####", "####.#####"]);
+        let piece = t_piece(2, 3, 1);
+        let t_spin = detect_t_spin(&board, &piece, Some(0));
+        assert_eq!(t_spin, TSpin::Full);
+        board.commit(&piece);
+        assert_eq!(board.full_lines(), vec![2, 3]);
+
+        for (level, points) in [(1, 1200), (4, 4800)] {
+            let clear = LineClear { lines: 2, t_spin, combo: 0, back_to_back: false, perfect_clear: false, level };
+            assert_eq!(GuidelineScoring.line_clear(&clear), points);
+        }
+    }
+
+    #[test]
+    fn huge_levels_and_combos_stop_at_the_largest_score() {
+        let clear = LineClear { lines: 4, t_spin: TSpin::None, combo: u32::MAX, back_to_back: true, perfect_clear: true, level: u32::MAX };
+        assert_eq!(GuidelineScoring.line_clear(&clear), i32::MAX);
+        assert_eq!(ClassicScoring.line_clear(&clear), i32::MAX);
+    }
+}
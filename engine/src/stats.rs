@@ -0,0 +1,22 @@
+/// Running totals for a game, kept for the game over screen and leaderboards.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameStats {
+    /// Pieces locked onto the board.
+    pub pieces_placed: u32,
+    /// Clears of four lines at once.
+    pub tetrises: u32,
+    /// Pieces locked with a T-spin, mini or full, whether or not they cleared lines.
+    pub t_spins: u32,
+    /// Garbage lines cleared, in modes that add garbage.
+    pub garbage_cleared: u32
+}
+
+impl GameStats {
+    /// Pieces placed per second over `elapsed_ms` of play.
+    pub fn pieces_per_second(&self, elapsed_ms: u64) -> f64 {
+        if elapsed_ms == 0 {
+            return 0.0;
+        }
+        self.pieces_placed as f64 * 1000.0 / elapsed_ms as f64
+    }
+}
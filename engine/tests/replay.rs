@@ -0,0 +1,198 @@
+use tetris_engine::replay::REPLAY_VERSION;
+use tetris_engine::{Board, GameInput, GameMode, GameState, GravityCurve, HoldMode, Piece, Replay, ReplayAction, Ruleset, SpawnPosition};
+
+/// Replays recorded by every earlier version of the format, oldest first. Each is a game played
+/// with that version's engine by a bot like the one below, clearing lines and scoring tetrises: a
+/// marathon in version 1, an ultra run to its time limit in version 3, a dig cleared in version 4
+/// and 20 line sprints otherwise, with hold used in versions 5 and 6 and the classic rules' line
+/// clear and entry delays in version 8.
+const OLD_REPLAYS: [&str; 8] = [
+    include_str!("fixtures/replay-v1.txt"),
+    include_str!("fixtures/replay-v2.txt"),
+    include_str!("fixtures/replay-v3.txt"),
+    include_str!("fixtures/replay-v4.txt"),
+    include_str!("fixtures/replay-v5.txt"),
+    include_str!("fixtures/replay-v6.txt"),
+    include_str!("fixtures/replay-v7.txt"),
+    include_str!("fixtures/replay-v8.txt")
+];
+
+/// Press and release `input`, recording both, with a few milliseconds of play after each.
+/// Returns whether the press did anything.
+fn tap(game: &mut GameState, replay: &mut Replay, input: GameInput) -> bool {
+    replay.record(game.elapsed_ms(), ReplayAction::Press(input));
+    let acted = game.press(input);
+    for _ in 0..8 {
+        game.tick();
+    }
+    replay.record(game.elapsed_ms(), ReplayAction::Release(input));
+    game.release(input);
+    for _ in 0..8 {
+        game.tick();
+    }
+    acted
+}
+
+/// Rate a board a piece just locked on and cleared `lines` from, higher being better. Keeps the
+/// stack low, flat and free of holes, and while it is low saves the rightmost column for tetrises.
+fn rate(board: &Board, lines: i16) -> f64 {
+    let (width, rows) = (board.width() as i8, board.total_height() as i8);
+    let heights: Vec<i32> = (0..width).map(|x| (0..rows).find(|&y| board.is_solid(x, y)).map_or(0, |y| (rows - y) as i32)).collect();
+    let holes: i32 = (0..width).map(|x| (rows - heights[x as usize] as i8..rows).filter(|&y| !board.is_solid(x, y)).count() as i32).sum();
+    let bumpiness: i32 = heights[..width as usize - 1].windows(2).map(|pair| (pair[0] - pair[1]).abs()).sum();
+    let mut rating = -0.51 * heights.iter().sum::<i32>() as f64 - 3.0 * holes as f64 - 0.18 * bumpiness as f64;
+    if heights.iter().all(|&height| height < 8) {
+        rating -= 2.0 * heights[width as usize - 1] as f64;
+        rating += if lines == 4 { 8.0 } else { -0.5 * lines as f64 };
+    }
+    else {
+        rating += 2.0 * lines as f64;
+    }
+    rating
+}
+
+/// Quarter turns to the right and column that place the current piece where `rate` likes it
+/// best, out of every spot it can be shifted to from where it is and dropped into.
+fn plan(game: &GameState) -> Option<(i8, i8)> {
+    let piece = *game.current_piece();
+    let board = game.board();
+    let mut best: Option<(f64, i8, i8)> = None;
+    for turns in 0..4 {
+        let rotation_state = (piece.rotation_state + turns) % 4;
+        let mask = piece.rotation[rotation_state as usize];
+        let repeats = (0..turns).any(|earlier| piece.rotation[((piece.rotation_state + earlier) % 4) as usize] == mask);
+        if repeats || board.collides(mask, piece.x, piece.y) {
+            continue;
+        }
+        for x in -3..board.width() as i8 {
+            let step = if x < piece.x { -1 } else { 1 };
+            let mut column = piece.x;
+            while column != x && !board.collides(mask, column + step, piece.y) {
+                column += step;
+            }
+            if column != x {
+                continue;
+            }
+            let mut y = piece.y;
+            while !board.collides(mask, x, y + 1) {
+                y += 1;
+            }
+            let mut after = board.clone();
+            after.commit(&Piece { rotation_state, x, y, ..piece });
+            let lines = after.remove_lines();
+            let rating = rate(&after, lines);
+            if best.is_none_or(|(best_rating, _, _)| rating > best_rating) {
+                best = Some((rating, turns, x));
+            }
+        }
+    }
+    best.map(|(_, turns, x)| (turns, x))
+}
+
+/// Play a game with a simple bot, recording every press and release, until it ends, clears
+/// 30 lines or runs for ten minutes. The bot places each piece where `plan` rates best,
+/// nudges and holds every fourth piece first, and waits `think_ms` before every piece.
+fn record_game(ruleset: Ruleset, mode: GameMode, seed: u64, think_ms: u64) -> (GameState, Replay) {
+    let mut game = GameState::with_mode(ruleset, mode, seed);
+    game.set_events_enabled(false);
+    game.start(seed);
+    let mut replay = Replay::new(seed, ruleset, mode);
+    let mut pieces = 0;
+    while game.is_playing() && game.lines_cleared_count() < 30 && game.elapsed_ms() < 600_000 {
+        while game.is_playing() && !game.is_piece_active() {
+            game.tick();
+        }
+        for _ in 0..think_ms {
+            game.tick();
+        }
+        pieces += 1;
+        if pieces % 4 == 0 {
+            tap(&mut game, &mut replay, GameInput::RotateRight);
+            tap(&mut game, &mut replay, GameInput::Left);
+            tap(&mut game, &mut replay, GameInput::Hold);
+        }
+        if let Some((turns, x)) = plan(&game) {
+            if turns == 3 {
+                tap(&mut game, &mut replay, GameInput::RotateLeft);
+            }
+            else {
+                for _ in 0..turns {
+                    tap(&mut game, &mut replay, GameInput::RotateRight);
+                }
+            }
+            while game.is_playing() && game.current_piece().x != x {
+                let input = if game.current_piece().x < x { GameInput::Right } else { GameInput::Left };
+                if !tap(&mut game, &mut replay, input) {
+                    break;
+                }
+            }
+        }
+        tap(&mut game, &mut replay, GameInput::HardDrop);
+    }
+    replay.finish(&game);
+    (game, replay)
+}
+
+#[test]
+fn write_and_read_round_trip() {
+    let ruleset = Ruleset { hold_mode: HoldMode::Unlimited, initial_rotation: false, board_width: 12, preview_count: 3, gravity: GravityCurve::Guideline, ..Ruleset::tgm() };
+    let mode = GameMode::Dig { rows: 8, messiness: 30, endless: true };
+    let (game, replay) = record_game(ruleset, mode, 42, 0);
+    assert_eq!(game.lines_cleared_count(), 30);
+
+    let mut text = Vec::new();
+    replay.write_to(&mut text).unwrap();
+    assert!(text.starts_with(format!("tetris-replay {}\n", REPLAY_VERSION).as_bytes()));
+    assert_eq!(Replay::read_from(text.as_slice()).unwrap(), replay);
+}
+
+#[test]
+fn play_reproduces_the_recorded_result() {
+    // The bot can't slide pieces along the stack under 20G, so the TGM rules get guideline gravity.
+    let cases = [
+        (Ruleset::guideline(), GameMode::Marathon, 0),
+        (Ruleset::classic(), GameMode::Sprint { lines: 20 }, 0),
+        (Ruleset { gravity: GravityCurve::Guideline, ..Ruleset::tgm() }, GameMode::Ultra { seconds: 30 }, 300)
+    ];
+    for (ruleset, mode, think_ms) in cases {
+        let (game, replay) = record_game(ruleset, mode, 5, think_ms);
+        assert!(game.level() > 1, "{} only reached level {}", mode.name(), game.level());
+        assert!(game.completed() || game.lines_cleared_count() >= 30, "{} did not reach its goal", mode.name());
+
+        let mut text = Vec::new();
+        replay.write_to(&mut text).unwrap();
+        let played = Replay::read_from(text.as_slice()).unwrap().play();
+        assert_eq!((played.score(), played.lines_cleared_count(), played.elapsed_ms()), (game.score(), game.lines_cleared_count(), game.elapsed_ms()), "{}", mode.name());
+    }
+}
+
+#[test]
+fn old_versions_reproduce_their_recorded_results() {
+    for (index, text) in OLD_REPLAYS.iter().enumerate() {
+        let version = index + 1;
+        let replay = Replay::read_from(text.as_bytes()).unwrap();
+        let result = replay.result.expect("fixture has a result");
+        assert!(result.lines_cleared_count > 0, "version {} fixture clears no lines", version);
+        let played = replay.play();
+        assert_eq!((played.score(), played.lines_cleared_count()), (result.score, result.lines_cleared_count), "version {}", version);
+        assert_eq!(played.elapsed_ms(), replay.end_ms, "version {}", version);
+    }
+}
+
+#[test]
+fn old_versions_fill_in_missing_settings() {
+    let ruleset = |version: usize| Replay::read_from(OLD_REPLAYS[version - 1].as_bytes()).unwrap().ruleset;
+    assert_eq!(ruleset(3).hidden_rows, 0);
+    assert_eq!(ruleset(4).preview_count, 1);
+    assert!(!ruleset(5).hold_reset);
+    assert!(ruleset(6).hold_reset);
+    assert_eq!(ruleset(6).spawn_position, SpawnPosition::Legacy);
+    assert_eq!(ruleset(7).spawn_position, SpawnPosition::Standard);
+    assert_eq!(ruleset(7).entry_delay_ms, 0);
+}
+
+#[test]
+fn unknown_versions_are_refused() {
+    let text = format!("tetris-replay {}\nseed 1\nevents\nend 0\n", REPLAY_VERSION + 1);
+    assert!(Replay::read_from(text.as_bytes()).is_err());
+}
@@ -0,0 +1,237 @@
+//! Local high score tables, one file per game mode.
+//!
+//! Tables live in the user's data directory as `highscores-<category>.txt`, where the
+//! category is the mode with its settings, such as `sprint-40`, followed by the board size
+//! for boards other than 10x20, such as `sprint-40-4x20`. Modes played against the clock
+//! rank completed games by time; everything else ranks by score.
+//!
+//! The first line is `tetris-highscores <version>`, followed by one tab separated line per
+//! entry, best first:
+//!
+//! ```text
+//! <score> <lines> <level> <duration ms> <date> <ruleset> <splits> <name>
+//! ```
+//!
+//! `<date>` is in seconds since the Unix epoch and `<ruleset>` is a preset name or `custom`.
+//! `<splits>` lists the milliseconds at which each line was cleared, separated by commas, for
+//! sprint runs, and is `-` for every other mode. Version 1 tables lack the `<splits>` field and
+//! are still read. Lines that can't be read are skipped, so a damaged file loses only the
+//! entries it damaged.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tetris_engine::{GameMode, GameState, Ruleset, BOARD_HEIGHT, BOARD_WIDTH};
+
+/// Entries kept in each table.
+pub const MAX_ENTRIES: usize = 10;
+/// Longest name that can be entered.
+pub const MAX_NAME_LEN: usize = 12;
+/// Version written to the first line of every table.
+const VERSION: u32 = 2;
+
+/// A finished game on a high score table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HighScore {
+    /// Name the player entered.
+    pub name: String,
+    /// Final score.
+    pub score: i32,
+    /// Lines cleared.
+    pub lines: i16,
+    /// Level the game ended on.
+    pub level: u32,
+    /// Simulated milliseconds the game lasted.
+    pub duration_ms: u64,
+    /// Seconds since the Unix epoch when the game ended.
+    pub date: u64,
+    /// Preset the game was played with, or `custom`.
+    pub ruleset: String,
+    /// Simulated milliseconds at which each line was cleared, kept for sprint runs so later runs
+    /// can compare their pace line by line. Empty for other modes and older tables.
+    pub splits: Vec<u64>
+}
+
+impl HighScore {
+    /// Entry for a finished game, dated now.
+    pub fn new(name: &str, game: &GameState) -> Self {
+        HighScore {
+            name: name.to_string(),
+            score: game.score(),
+            lines: game.lines_cleared_count(),
+            level: game.level(),
+            duration_ms: game.elapsed_ms(),
+            date: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0),
+            ruleset: game.ruleset().preset_name().unwrap_or("custom").to_string(),
+            splits: match game.mode() {
+                GameMode::Sprint { .. } => game.line_times().to_vec(),
+                _ => Vec::new()
+            }
+        }
+    }
+
+    /// Read an entry from one line of a table written in the given version of the format.
+    fn parse(line: &str, version: u32) -> Option<Self> {
+        let mut fields = line.splitn(if version >= 2 { 8 } else { 7 }, '\t');
+        Some(HighScore {
+            score: fields.next()?.parse().ok()?,
+            lines: fields.next()?.parse().ok()?,
+            level: fields.next()?.parse().ok()?,
+            duration_ms: fields.next()?.parse().ok()?,
+            date: fields.next()?.parse().ok()?,
+            ruleset: fields.next()?.to_string(),
+            splits: if version >= 2 { parse_splits(fields.next()?)? } else { Vec::new() },
+            name: fields.next()?.to_string()
+        })
+    }
+
+    /// Milliseconds into this run at which it had cleared `lines` lines, if its splits go that far.
+    pub fn split(&self, lines: usize) -> Option<u64> {
+        match lines {
+            0 => Some(0),
+            _ => self.splits.get(lines - 1).copied()
+        }
+    }
+}
+
+/// The best games of one mode, best first.
+#[derive(Clone, Debug)]
+pub struct HighScoreTable {
+    /// File the table is saved to, or `None` if there is no data directory.
+    path: Option<PathBuf>,
+    /// Are entries ranked by the fastest time rather than the highest score.
+    by_time: bool,
+    /// Entries, best first, at most `MAX_ENTRIES` of them.
+    entries: Vec<HighScore>
+}
+
+impl HighScoreTable {
+    /// Path of the table for the given mode and the ruleset's board size in the user's data directory.
+    pub fn path_for(mode: GameMode, ruleset: &Ruleset) -> Option<PathBuf> {
+        let mut category = mode.category();
+        if (ruleset.board_width, ruleset.board_height) != (BOARD_WIDTH, BOARD_HEIGHT) {
+            category = format!("{}-{}x{}", category, ruleset.board_width, ruleset.board_height);
+        }
+        directories::ProjectDirs::from("", "", "tetris").map(|dirs| dirs.data_dir().join(format!("highscores-{}.txt", category)))
+    }
+
+    /// Load the table for the given mode and board size. A missing file gives an empty table, and an
+    /// unreadable or damaged one is reported on stderr and keeps whatever entries survived.
+    pub fn load(mode: GameMode, ruleset: &Ruleset) -> Self {
+        let path = HighScoreTable::path_for(mode, ruleset);
+        let mut table = HighScoreTable { path: path.clone(), by_time: mode.ranks_by_time(), entries: Vec::new() };
+        let Some(path) = path else {
+            return table;
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return table,
+            Err(error) => {
+                eprintln!("Could not read high scores from {}: {}", path.display(), error);
+                return table;
+            }
+        };
+
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let version = lines.next().and_then(|line| line.strip_prefix("tetris-highscores ")).and_then(|version| version.parse().ok());
+        let Some(version @ 1..=VERSION) = version else {
+            eprintln!("{} is not a version 1 to {} high score table, starting a new one.", path.display(), VERSION);
+            return table;
+        };
+        let mut skipped = 0;
+        for line in lines {
+            match HighScore::parse(line, version) {
+                Some(entry) => table.entries.push(entry),
+                None => skipped += 1
+            }
+        }
+        if skipped > 0 {
+            eprintln!("Skipped {} damaged high score entries in {}.", skipped, path.display());
+        }
+        table.entries.sort_by(|a, b| if table.by_time { a.duration_ms.cmp(&b.duration_ms) } else { b.score.cmp(&a.score) });
+        table.entries.truncate(MAX_ENTRIES);
+        table
+    }
+
+    /// Entries, best first.
+    pub fn entries(&self) -> &[HighScore] { &self.entries }
+
+    /// The best entry, which is the personal best for modes ranked by time.
+    pub fn best(&self) -> Option<&HighScore> { self.entries.first() }
+
+    /// Would the finished game make it onto the table. Modes ranked by time only take
+    /// games that reached their goal, and modes ranked by score only games that scored.
+    pub fn qualifies(&self, game: &GameState) -> bool {
+        let counts = if self.by_time { game.completed() } else { game.score() > 0 };
+        let candidate = HighScore::new("", game);
+        counts && (self.entries.len() < MAX_ENTRIES || self.entries.last().is_some_and(|last| self.is_better(&candidate, last)))
+    }
+
+    /// Does `entry` rank strictly above `other`.
+    fn is_better(&self, entry: &HighScore, other: &HighScore) -> bool {
+        if self.by_time {
+            entry.duration_ms < other.duration_ms
+        }
+        else {
+            entry.score > other.score
+        }
+    }
+
+    /// Add an entry and return its index on the table, or `None` if it didn't make the cut.
+    /// Ties go below the entries already on the table.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let index = self.entries.iter().position(|other| self.is_better(&entry, other)).unwrap_or(self.entries.len());
+        if index >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(index, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(index)
+    }
+
+    /// Write the table to its file. The new table is written next to the old one and then
+    /// renamed over it, so a crash mid-write never leaves a half written table behind.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp_path = path.with_extension("txt.tmp");
+        let mut file = fs::File::create(&temp_path)?;
+        writeln!(file, "tetris-highscores {}", VERSION)?;
+        for entry in &self.entries {
+            let name: String = entry.name.chars().filter(|c| !c.is_control()).collect();
+            let splits = if entry.splits.is_empty() { "-".to_string() } else { entry.splits.iter().map(u64::to_string).collect::<Vec<_>>().join(",") };
+            writeln!(file, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", entry.score, entry.lines, entry.level, entry.duration_ms, entry.date, entry.ruleset, splits, name)?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+/// Read the comma separated split times of one entry, or none for `-`.
+fn parse_splits(field: &str) -> Option<Vec<u64>> {
+    match field {
+        "-" => Some(Vec::new()),
+        _ => field.split(',').map(|split| split.parse().ok()).collect()
+    }
+}
+
+/// Format seconds since the Unix epoch as a `YYYY-MM-DD` date in UTC.
+pub fn format_date(secs: u64) -> String {
+    // Civil date from a day count, after Howard Hinnant's `civil_from_days`.
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
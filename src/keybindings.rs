@@ -0,0 +1,225 @@
+//! Keyboard bindings for the window frontend.
+//!
+//! Bindings are read from `keys.toml` in the user's config directory, with one line per
+//! action listing the keys bound to it:
+//!
+//! ```toml
+//! left = ["Left", "A"]
+//! hold = ["C", "LShift"]
+//! start = "Space"
+//! ```
+//!
+//! Actions use the names from `GameInput::name` and keys use ggez's `KeyCode` names,
+//! ignoring case. Actions left out of the file keep their default keys. Escape always pauses
+//! and opens the menu, so it cannot be bound. H shows the high scores on the title screen and
+//! Q quits a paused game, so they can be bound to anything but `start`.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ggez::input::keyboard::KeyCode;
+use tetris_engine::GameInput;
+
+/// Name of the bindings file inside the config directory.
+const FILE_NAME: &str = "keys.toml";
+
+/// Keys the frontend handles itself before looking at the bindings: Escape pauses and opens the menu.
+const RESERVED_KEYS: [KeyCode; 1] = [KeyCode::Escape];
+
+/// Keys the frontend handles itself on the title screen and in pause, where `start` is the only
+/// bound action that does anything: H shows the high scores and Q quits a paused game.
+const START_RESERVED_KEYS: [KeyCode; 2] = [KeyCode::H, KeyCode::Q];
+
+/// Keys that can be named in the bindings file.
+const BINDABLE_KEYS: [KeyCode; 72] = [
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H,
+    KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P,
+    KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X,
+    KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5, KeyCode::Key6,
+    KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4, KeyCode::Numpad5,
+    KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down,
+    KeyCode::Space, KeyCode::Return, KeyCode::Tab, KeyCode::Back, KeyCode::Insert, KeyCode::Delete,
+    KeyCode::Home, KeyCode::End, KeyCode::PageUp, KeyCode::PageDown,
+    KeyCode::LShift, KeyCode::RShift, KeyCode::LControl, KeyCode::RControl, KeyCode::LAlt, KeyCode::RAlt,
+    KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon, KeyCode::Apostrophe, KeyCode::Minus
+];
+
+/// Problems found while reading a bindings file.
+#[derive(Debug)]
+pub enum KeyBindingError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not valid TOML.
+    Syntax(String),
+    /// A line names an action that does not exist.
+    UnknownAction(String),
+    /// An action's value is not a key name or a list of key names.
+    BadValue(String),
+    /// A key name is not one of the bindable keys.
+    UnknownKey { action: String, key: String },
+    /// A key the frontend keeps for itself is bound to an action it would never reach.
+    ReservedKey { action: String, key: KeyCode },
+    /// The same key is bound to two actions.
+    Conflict { key: KeyCode, first: GameInput, second: GameInput }
+}
+
+impl fmt::Display for KeyBindingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyBindingError::Io(error) => write!(f, "could not read key bindings: {}", error),
+            KeyBindingError::Syntax(message) => write!(f, "key bindings are not valid TOML: {}", message),
+            KeyBindingError::UnknownAction(action) => write!(f, "unknown action '{}'", action),
+            KeyBindingError::BadValue(action) => write!(f, "'{}' should be a key name or a list of key names", action),
+            KeyBindingError::UnknownKey { action, key } => write!(f, "unknown key '{}' for '{}'", key, action),
+            KeyBindingError::ReservedKey { action, key } => write!(f, "key '{:?}' for '{}' is reserved for {}", key, action, reserved_use(*key)),
+            KeyBindingError::Conflict { key, first, second } => write!(f, "key '{:?}' is bound to both '{}' and '{}'", key, first.name(), second.name())
+        }
+    }
+}
+
+impl std::error::Error for KeyBindingError {}
+
+/// Which input every bound key sends to the game.
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    /// Keys bound to each action. A key appears under at most one action.
+    keys: Vec<(GameInput, Vec<KeyCode>)>
+}
+
+impl KeyBindings {
+    /// The input bound to the given key, if any.
+    pub fn input_for(&self, key: KeyCode) -> Option<GameInput> {
+        self.keys.iter().find(|(_, keys)| keys.contains(&key)).map(|&(input, _)| input)
+    }
+
+    /// Keys bound to the given input.
+    pub fn keys_for(&self, input: GameInput) -> &[KeyCode] {
+        self.keys.iter().find(|&&(bound, _)| bound == input).map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    /// Path of the bindings file in the user's config directory.
+    pub fn default_path() -> Option<PathBuf> {
+        directories::ProjectDirs::from("", "", "tetris").map(|dirs| dirs.config_dir().join(FILE_NAME))
+    }
+
+    /// Load the bindings from the user's config directory. A missing file gives the defaults.
+    /// Any problem with the file is reported on stderr and the defaults are used instead.
+    pub fn load_or_default() -> Self {
+        let Some(path) = KeyBindings::default_path() else {
+            return KeyBindings::default();
+        };
+        if !path.exists() {
+            return KeyBindings::default();
+        }
+        KeyBindings::load(&path).unwrap_or_else(|errors| {
+            for error in errors {
+                eprintln!("{}: {}", path.display(), error);
+            }
+            eprintln!("Using the default key bindings.");
+            KeyBindings::default()
+        })
+    }
+
+    /// Read bindings from the file at `path`, reporting every bad entry found.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Vec<KeyBindingError>> {
+        let text = fs::read_to_string(path).map_err(|error| vec![KeyBindingError::Io(error)])?;
+        KeyBindings::parse(&text)
+    }
+
+    /// Parse bindings in the format described at the top of this module.
+    pub fn parse(text: &str) -> Result<Self, Vec<KeyBindingError>> {
+        let table: toml::value::Table = toml::from_str(text).map_err(|error| vec![KeyBindingError::Syntax(error.to_string())])?;
+        let mut bindings = KeyBindings::default();
+        let mut errors = Vec::new();
+
+        for (action, value) in &table {
+            let Some(input) = GameInput::from_name(action) else {
+                errors.push(KeyBindingError::UnknownAction(action.clone()));
+                continue;
+            };
+            let names = match value {
+                toml::Value::String(name) => vec![name.as_str()],
+                toml::Value::Array(values) if values.iter().all(toml::Value::is_str) => values.iter().filter_map(toml::Value::as_str).collect(),
+                _ => {
+                    errors.push(KeyBindingError::BadValue(action.clone()));
+                    continue;
+                }
+            };
+            let mut keys = Vec::new();
+            for name in names {
+                match key_from_name(name) {
+                    Some(key) if input == GameInput::Start && START_RESERVED_KEYS.contains(&key) => {
+                        errors.push(KeyBindingError::ReservedKey { action: action.clone(), key });
+                    }
+                    Some(key) if !keys.contains(&key) => keys.push(key),
+                    Some(_) => {}
+                    None => match reserved_key_from_name(name) {
+                        Some(key) => errors.push(KeyBindingError::ReservedKey { action: action.clone(), key }),
+                        None => errors.push(KeyBindingError::UnknownKey { action: action.clone(), key: name.to_string() })
+                    }
+                }
+            }
+            if let Some((_, bound)) = bindings.keys.iter_mut().find(|(bound, _)| *bound == input) {
+                *bound = keys;
+            }
+        }
+
+        // A key can only send one input; report every key claimed by two actions.
+        for (index, (first, keys)) in bindings.keys.iter().enumerate() {
+            for (second, other_keys) in &bindings.keys[index + 1..] {
+                for &key in keys.iter().filter(|key| other_keys.contains(key)) {
+                    errors.push(KeyBindingError::Conflict { key, first: *first, second: *second });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(bindings)
+        }
+        else {
+            Err(errors)
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    /// Guideline style arrows, Z/X and C/Shift alongside the original WASD and J/K layout.
+    fn default() -> Self {
+        KeyBindings {
+            keys: vec![
+                (GameInput::Left, vec![KeyCode::Left, KeyCode::A]),
+                (GameInput::Right, vec![KeyCode::Right, KeyCode::D]),
+                (GameInput::Down, vec![KeyCode::Down, KeyCode::S]),
+                (GameInput::HardDrop, vec![KeyCode::Up, KeyCode::W]),
+                (GameInput::RotateLeft, vec![KeyCode::Z, KeyCode::J]),
+                (GameInput::RotateRight, vec![KeyCode::X, KeyCode::K]),
+                (GameInput::Hold, vec![KeyCode::C, KeyCode::LShift, KeyCode::RShift, KeyCode::E]),
+                (GameInput::Start, vec![KeyCode::Space, KeyCode::Return])
+            ]
+        }
+    }
+}
+
+/// The bindable key with the given `KeyCode` name, ignoring case.
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.into_iter().find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+/// The reserved key with the given `KeyCode` name, ignoring case.
+fn reserved_key_from_name(name: &str) -> Option<KeyCode> {
+    RESERVED_KEYS.into_iter().find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+/// What the frontend uses a reserved key for.
+fn reserved_use(key: KeyCode) -> &'static str {
+    match key {
+        KeyCode::H => "the high scores",
+        KeyCode::Q => "quitting a paused game",
+        _ => "pausing and the menu"
+    }
+}
@@ -1,9 +1,13 @@
 use ggez::{event, graphics, Context, GameResult};
 use ggez::input::keyboard::{KeyCode, KeyInput};
-use rand::Rng;
 use ggez::graphics::{Canvas, Color};
-extern crate stopwatch;
-use stopwatch::{Stopwatch};
+use std::path::{Path, PathBuf};
+use highscores::{HighScore, HighScoreTable};
+use keybindings::KeyBindings;
+use tetris_engine::{format_time, mask_cells, new_seed, GameEvent, GameInput, GameMode, GameState, PieceColor, PlayState, Replay, ReplayAction, ReplayPlayer, Ruleset, TopOut};
+
+mod highscores;
+mod keybindings;
 
 // Next we define how large we want our actual window to be by multiplying
 // the components of our grid size by its corresponding pixel size.
@@ -11,467 +15,440 @@ const SCREEN_SIZE: (f32, f32) = (
     540.0,
     610.0,
 );
+/// Left edge of the board, leaving room for the stats to its left.
+const BOARD_LEFT: f32 = 100.0;
+/// Room kept to the right of the board for the next and hold boxes.
+const SIDE_PANEL_WIDTH: f32 = 130.0;
+/// Size of a block in the hold box and the first box of the next queue.
+const PREVIEW_CELL_SIZE: f32 = 30.0;
+/// Size of a block in the smaller boxes further down the next queue.
+const QUEUE_CELL_SIZE: f32 = 12.0;
+
+/// Entries of the menu, in the order they are listed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum MenuItem {
+    NewGame,
+    Mode,
+    Title,
+    Exit
+}
 
-/// Different colors a piece can be.
-#[derive(Copy, Clone)]
-enum PieceColor {
-    Red,    /// Z
-    Yellow, /// U
-    Blue,   /// J
-    Cyan,   /// I
-    Orange, /// L
-    Green,  /// S
-    Purple, /// T
-    Black,  /// None
-    Gray
+impl MenuItem {
+    /// Every menu entry, in the order they are listed.
+    const ALL: [MenuItem; 4] = [MenuItem::NewGame, MenuItem::Mode, MenuItem::Title, MenuItem::Exit];
+
+    /// Text shown for the entry.
+    fn label(self) -> &'static str {
+        match self {
+            MenuItem::NewGame => "New Game",
+            MenuItem::Mode => "Mode",
+            MenuItem::Title => "Title Screen",
+            MenuItem::Exit => "Exit"
+        }
+    }
 }
 
-/// Main state of the game.
-struct GameState {
-    /// Current piece being manipulated on the board.
-    current_piece: Piece,
-    /// Next piece that will be used after the current piece is fully dropped.
-    next_piece: Piece,
-    /// Held piece that can be swapped out for during play.
-    hold_piece: Option<Piece>,
-    /// Lines cleared during play.
-    lines_cleared_count: i16,
-    /// Score accumulated throughout play.
-    score: i32,
-    /// Has the player held the piece since the current piece has been dropped.
-    has_held_a_piece: bool,
-    /// Global timer used to measure time between auto-drop.
-    global_timer: Stopwatch,
-    /// Last auto drop intermediate value.
-    last_piece_dropped_time: i64,
-    /// Time since last FPS poll.
-    last_fps_poll_time: i64,
-    /// FPS Counter.
-    fps_count: i64,
-    /// Displayed FPS Counter.
-    display_fps: i64,
-    /// Is the game currently being played.
-    is_playing: bool,
-    /// Board where pieces are placed/represented.
-    board: [[Option<PieceColor>; 20]; 10]
+/// Frontend state wrapping the headless engine.
+struct MainState {
+    /// Engine holding the board, pieces and score.
+    game: GameState,
+    /// Seed every game is started from, or `None` to pick a fresh one each game.
+    seed: Option<u64>,
+    /// File every finished game is recorded to.
+    record_path: Option<PathBuf>,
+    /// Recording of the game in progress.
+    recording: Option<Replay>,
+    /// Replay being played back instead of taking keyboard input.
+    playback: Option<ReplayPlayer>,
+    /// Which input each key sends to the game.
+    keys: KeyBindings,
+    /// Resume a game paused by losing focus once the window gets it back.
+    auto_resume: bool,
+    /// Was the game paused because the window lost focus, rather than by the player.
+    paused_by_focus: bool,
+    /// Index into `MenuItem::ALL` of the highlighted menu entry.
+    menu_selection: usize,
+    /// Best games played in this mode.
+    high_scores: HighScoreTable,
+    /// Name typed so far when the last game made the high score table.
+    name_entry: Option<String>,
+    /// Place on the high score table the last game was entered at.
+    last_rank: Option<usize>,
+    /// Is the title screen showing the high score table.
+    show_high_scores: bool,
+    /// Completed rows flashing until they collapse, counted like the board's rows.
+    clearing_rows: Vec<usize>
 }
 
-/// Main state of the game.
-impl GameState {
-    /// Constructor for the GameState struct.
-    pub fn new() -> Self {
-        GameState {
-            lines_cleared_count: 0,
-            score: 0,
-            has_held_a_piece: false,
-            current_piece: Piece::get_piece(),
-            next_piece: Piece::get_piece(),
-            hold_piece: None,
-            global_timer: Stopwatch::start_new(),
-            last_piece_dropped_time: 0,
-            last_fps_poll_time: 0,
-            fps_count: 0,
-            display_fps: 0,
-            is_playing: false,
-            board: [[None; 20]; 10]
+impl MainState {
+    /// Constructor for the MainState struct.
+    pub fn new(ruleset: Ruleset, mode: GameMode, seed: Option<u64>, record_path: Option<PathBuf>, playback: Option<Replay>, keys: KeyBindings, auto_resume: bool) -> Self {
+        MainState {
+            game: GameState::with_mode(ruleset, mode, seed.unwrap_or_else(new_seed)),
+            seed,
+            record_path,
+            recording: None,
+            playback: playback.map(ReplayPlayer::new),
+            keys,
+            auto_resume,
+            paused_by_focus: false,
+            menu_selection: 0,
+            high_scores: HighScoreTable::load(mode, &ruleset),
+            name_entry: None,
+            last_rank: None,
+            show_high_scores: false,
+            clearing_rows: Vec::new()
         }
     }
 
-    /// Move a piece left, right or down one block.
-    pub fn move_direction(&mut self, direction: GameInput) -> bool {
-        let mut x: i8 = self.current_piece.x;
-        let mut y: i8 = self.current_piece.y;
-
-        if direction == GameInput::Left {
-            x = x - 1;
-        }
-        else if direction == GameInput::Right {
-            x = x + 1;
+    /// Start a new game, or restart the replay when playing one back.
+    fn start_game(&mut self) {
+        self.clearing_rows.clear();
+        if let Some(player) = &mut self.playback {
+            self.game = player.start();
+            return;
         }
-        else if direction == GameInput::Down {
-            y = y + 1;
-        }
-
-        if !GameState::check_collision(self, x, y) {
-            self.current_piece.x = x;
-            self.current_piece.y = y;
-            return true;
+        self.last_rank = None;
+        let seed = self.seed.unwrap_or_else(new_seed);
+        self.game.start(seed);
+        if self.record_path.is_some() {
+            self.recording = Some(Replay::new(seed, *self.game.ruleset(), self.game.mode()));
         }
-        return false;
     }
 
-    /// Move the piece down one block.
-    pub fn move_down(&mut self, is_holding_down: bool) -> bool {
-        if is_holding_down {
-            self.score = self.score + 10
+    /// Wrap up a game that just topped out: keep its recording and ask for a name if it made the high score table.
+    fn game_over(&mut self) {
+        self.save_recording();
+        if self.playback.is_none() && self.high_scores.qualifies(&self.game) {
+            self.name_entry = Some(String::new());
         }
+    }
 
-        if !self.move_direction(GameInput::Down) {
-            self.after_drop_collision();
+    /// Handle a key while the player types their name for the high score table.
+    /// Enter saves the entry and Escape skips it.
+    fn name_entry_key(&mut self, key: KeyCode) {
+        let Some(name) = &mut self.name_entry else {
+            return;
+        };
+        match key {
+            KeyCode::Back => {
+                name.pop();
+            }
+            KeyCode::Return => {
+                let name = if name.trim().is_empty() { "Player".to_string() } else { name.trim().to_string() };
+                self.last_rank = self.high_scores.insert(HighScore::new(&name, &self.game));
+                if let Err(error) = self.high_scores.save() {
+                    eprintln!("Could not save high scores: {}", error);
+                }
+                self.name_entry = None;
+            }
+            KeyCode::Escape => self.name_entry = None,
+            _ => {}
         }
-        return true;
     }
 
-    /// Drop a piece straight down until collision and add score for each block passed.
-    pub fn hard_drop(&mut self) -> bool {
-        while self.move_direction(GameInput::Down) {
-            self.score = self.score + 10
-        }
-        self.score = self.score + 10;
-        self.after_drop_collision();
+    /// Stop the current game, keeping its recording, and open the menu.
+    fn open_menu(&mut self) {
+        self.save_recording();
+        self.game.quit_to_menu();
+        self.menu_selection = 0;
+    }
 
-        return true;
+    /// Switch to the next or previous mode in `GameMode::ALL`, along with its high score table.
+    fn cycle_mode(&mut self, forward: bool) {
+        let count = GameMode::ALL.len();
+        let index = GameMode::ALL.iter().position(|&mode| mode == self.game.mode()).unwrap_or(0);
+        let mode = GameMode::ALL[if forward { (index + 1) % count } else { (index + count - 1) % count }];
+        self.game = GameState::with_mode(*self.game.ruleset(), mode, self.game.seed());
+        self.game.quit_to_menu();
+        self.high_scores = HighScoreTable::load(mode, self.game.ruleset());
+        self.last_rank = None;
     }
 
-    /// Handle the current piece after a collision occurs from being dropped.
-    pub fn after_drop_collision(&mut self) {
-        self.commit_piece_to_board();
-        self.remove_lines();
-        self.current_piece = self.next_piece;
-        self.next_piece = Piece::get_piece();
-        self.has_held_a_piece = false;
-        if self.check_collision(self.current_piece.x, self.current_piece.y) {
-            self.is_playing = false;
+    /// Move the menu highlight with the arrow keys and act on the highlighted entry with Enter or Space.
+    /// Left and right change the mode when it is highlighted.
+    fn menu_key(&mut self, ctx: &mut Context, key: KeyCode) {
+        let count = MenuItem::ALL.len();
+        let item = MenuItem::ALL[self.menu_selection];
+        match key {
+            KeyCode::Up => self.menu_selection = (self.menu_selection + count - 1) % count,
+            KeyCode::Down => self.menu_selection = (self.menu_selection + 1) % count,
+            KeyCode::Left | KeyCode::Right if item == MenuItem::Mode => self.cycle_mode(key == KeyCode::Right),
+            KeyCode::Return | KeyCode::Space => match item {
+                MenuItem::NewGame => self.start_game(),
+                MenuItem::Mode => self.cycle_mode(true),
+                MenuItem::Title => self.game.return_to_title(),
+                MenuItem::Exit => ctx.request_quit()
+            },
+            _ => {}
         }
     }
 
-    /// Calculate what lines need removed and add score/remove lines accordingly.
-    fn remove_lines(&mut self) {
-        let mut n = 0;
-        let mut y = 19;
-
-        while y > 0 {
-            let mut is_line_complete = true;
-            let mut x = 0;
-            while x < 10 {
-                is_line_complete = is_line_complete & !self.board[x][y].is_none();
-                x = x + 1;
+    /// Take the game's events, tracking the rows being cleared and wrapping up the game once it ends.
+    /// Game overs are picked up from the events rather than the play state, so each is handled once
+    /// whether it came from stepping the game or from a key press.
+    fn handle_events(&mut self) {
+        let mut ended = false;
+        for event in self.game.drain_events() {
+            match event {
+                GameEvent::LinesMarked { rows } => self.clearing_rows = rows,
+                GameEvent::LinesCollapsed { .. } => self.clearing_rows.clear(),
+                GameEvent::GameOver { .. } => ended = true,
+                _ => {}
             }
-
-            if is_line_complete {
-                self.remove_line(y as i8);
-                y = y + 1;
-                n = n + 1;
-            }
-
-            y = y - 1;
         }
-
-        if n > 0 {
-            self.lines_cleared_count = self.lines_cleared_count + n;
-            self.score = self.score + ((i16::pow(n, 2) * 100) as i32);
+        if ended {
+            self.game_over();
         }
     }
 
-    /// Remove given line and shift all the lines 'above' down.
-    fn remove_line(&mut self, mut n: i8) {
-        loop {
-            let mut x = 0;
-            while x < 10 {
-                self.board[x as usize][n as usize] = if n == 0 { None } else { self.board[x as usize][(n - 1) as usize] };
-                x = x + 1
+    /// Write the recording of the current game next to the record path, if one is running.
+    fn save_recording(&mut self) {
+        if let (Some(mut replay), Some(record_path)) = (self.recording.take(), self.record_path.as_ref()) {
+            replay.finish(&self.game);
+            let path = free_record_path(record_path);
+            match replay.save(&path) {
+                Ok(()) => eprintln!("Saved replay to {}.", path.display()),
+                Err(error) => eprintln!("Could not save replay to {}: {}", path.display(), error)
             }
-            if n == 0 {
-                break;
-            }
-            n = n - 1;
         }
     }
 
-    /// After collision when being dropped set the positions on the board to the current piece.
-    pub fn commit_piece_to_board(&mut self) {
-        let mut iterations = 0;
-        let mut func = |x: i8, y: i8| {
-            self.board[x as usize][y as usize] = Option::from(self.current_piece.piece_color);
-        };
-        while iterations < 16
-        {
-            if (self.current_piece.get_rotation_state() & (0x8000 >> iterations)) > 0 {
-                func(self.current_piece.x + (iterations % 4), self.current_piece.y + (iterations / 4));
+    /// Label and value of every line of the in-game display, which depends on the mode.
+    /// Sprint counts lines down and shows how far ahead of or behind the personal best the run is,
+    /// ultra counts the clock down and dig counts the garbage left, or cleared in an endless dig.
+    fn hud_stats(&self) -> Vec<(&'static str, String)> {
+        let elapsed_ms = self.game.elapsed_ms();
+        let lines = self.game.lines_cleared_count();
+        let mut hud = vec![("SCORE:", self.game.score().to_string())];
+        match self.game.mode() {
+            GameMode::Marathon => {
+                hud.push(("LINES:", lines.to_string()));
+                hud.push(("LEVEL:", self.game.level().to_string()));
+                hud.push(("TIME:", format_time(elapsed_ms)));
             }
-            iterations = iterations + 1;
-        }
-    }
-
-    /// Set current piece as the hold piece and swap out a new piece if there isn't one in the current hold.
-    pub fn hold(&mut self) -> bool {
-        if !self.has_held_a_piece {
-            self.has_held_a_piece = true;
-
-            if self.hold_piece.is_some() {
-                let temp = match self.hold_piece { None => Piece::get_piece(), Some(temp) => temp };
-                self.hold_piece = Option::from(self.current_piece);
-                self.current_piece = temp;
+            GameMode::Sprint { lines: target } => {
+                hud.push(("LINES:", format!("{}/{}", lines, target)));
+                hud.push(("TIME:", format_time(elapsed_ms)));
+                let pace = match self.high_scores.best() {
+                    // Where the best run was after clearing the same number of lines. Entries saved before
+                    // splits were kept fall back to an estimate that assumes the best run kept an even pace.
+                    Some(best) => {
+                        let lines = lines.max(0) as usize;
+                        let best_ms = best.split(lines).unwrap_or(best.duration_ms * lines as u64 / target.max(1) as u64) as i64;
+                        format!("{:+.2}s", (elapsed_ms as i64 - best_ms) as f64 / 1000.0)
+                    }
+                    None => "no PB".to_string()
+                };
+                hud.push(("PACE:", pace));
             }
-            else {
-                self.hold_piece = Option::from(self.current_piece);
-                self.current_piece = self.next_piece;
-                self.next_piece = Piece::get_piece();
+            GameMode::Ultra { .. } => {
+                let limit_ms = self.game.mode().time_limit_ms().unwrap_or(0);
+                hud.push(("LINES:", lines.to_string()));
+                hud.push(("LEVEL:", self.game.level().to_string()));
+                hud.push(("TIME LEFT:", format_time(limit_ms.saturating_sub(elapsed_ms))));
+            }
+            GameMode::Dig { endless: false, .. } => {
+                hud.push(("GARBAGE:", self.game.board().garbage_lines().to_string()));
+                hud.push(("LINES:", lines.to_string()));
+                hud.push(("TIME:", format_time(elapsed_ms)));
+            }
+            GameMode::Dig { endless: true, .. } => {
+                hud.push(("DUG:", self.game.stats().garbage_cleared.to_string()));
+                hud.push(("LINES:", lines.to_string()));
+                hud.push(("TIME:", format_time(elapsed_ms)));
             }
-            let mut hold_piece_as_some = match self.hold_piece { None => Piece::get_piece(), Some(temp) => temp };
-            hold_piece_as_some.x = 4;
-            hold_piece_as_some.y = 0;
-
-            self.last_piece_dropped_time = self.global_timer.elapsed_ms();
         }
-        return true;
+        hud
     }
 
-    /// Rotates the given piece if there is no collision.
-    pub fn rotate(&mut self, direction: GameInput) -> bool {
-        let old_rotation_state = self.current_piece.rotation_state;
-        self.current_piece.rotation_state = match direction {
-            GameInput::RotateLeft => (self.current_piece.rotation_state + 3) % 4,
-            GameInput::RotateRight => (self.current_piece.rotation_state + 1) % 4,
-            _ => self.current_piece.rotation_state
-        };
-
-        if GameState::check_collision(self, self.current_piece.x, self.current_piece.y) {
-            self.current_piece.rotation_state = old_rotation_state;
-        }
-        return true;
+    /// Label and value of every line of the game over screen.
+    fn final_stats(&self) -> [(&'static str, String); 8] {
+        let stats = self.game.stats();
+        let elapsed_ms = self.game.elapsed_ms();
+        [
+            ("SCORE:", self.game.score().to_string()),
+            ("LINES:", self.game.lines_cleared_count().to_string()),
+            ("LEVEL:", self.game.level().to_string()),
+            ("PIECES:", stats.pieces_placed.to_string()),
+            ("PPS:", format!("{:.2}", stats.pieces_per_second(elapsed_ms))),
+            ("TIME:", format_time(elapsed_ms)),
+            ("TETRISES:", stats.tetrises.to_string()),
+            ("T-SPINS:", stats.t_spins.to_string())
+        ]
     }
 
-    /// Calculates the y position of the drop shadow.
-    pub fn get_drop_shadow_y(&self) -> i8 {
-        let mut y: i8 = self.current_piece.y;
-        while !GameState::check_collision(self, self.current_piece.x, y) {
-            y = y + 1;
+    /// Report whether a finished playback reproduced the recorded result.
+    fn check_playback(&self) {
+        if let Some(result) = self.playback.as_ref().and_then(|player| player.replay().result) {
+            let matches = result.score == self.game.score() && result.lines_cleared_count == self.game.lines_cleared_count();
+            println!("Replay finished with score {} and {} lines ({}).", self.game.score(), self.game.lines_cleared_count(),
+                if matches { "matches the recording" } else { "DOES NOT match the recording" });
         }
-        return y - 1;
     }
 
-    /// Checks if the current piece collides with another block given a different x/y value.
-    pub fn check_collision(&self, x: i8, y: i8) -> bool {
-        let mut b: bool = false;
-        let mut iterations = 0;
-        let mut func = |x: i8, y: i8| {
-
-            if (x < 0) || (x >= 10) || (y < 0) || (y >= 20) {
-                b = b | true;
-            }
-            else {
-                b = b | !self.board[x as usize][y as usize].is_none();
-            }
-        };
-        while iterations < 16
-        {
-            if (self.current_piece.rotation[self.current_piece.rotation_state as usize] & (0x8000 >> iterations)) > 0 {
-                func(x + (iterations % 4), y + (iterations / 4));
-            }
-            iterations = iterations + 1;
-        }
-        return b;
+    /// Size of a board cell in pixels, the largest whole number that fits the board into the window.
+    fn cell_size(&self, ctx: &Context) -> f32 {
+        let (width, height) = ctx.gfx.drawable_size();
+        let board = self.game.board();
+        let fit = ((width - BOARD_LEFT - SIDE_PANEL_WIDTH) / board.width() as f32).min(height / board.height() as f32);
+        fit.floor().max(1.0)
     }
 
-    /// Draws the board to the canvas.
+    /// Draws the visible rows of the board to the canvas, with cells scaled to fit the window.
     /// Also draws the current piece, the current piece's shadow, and
     /// the hold/next boxes.
-    pub fn draw_board(&self, mut canvas: &mut Canvas) {
-
-        let mut y: i8 = 0;
-
-        while y < 20 {
-            let mut x: i8 = 0;
-            while x < 10 {
-                let piece_color = match self.board[x as usize][y as usize] { None => PieceColor::Black, Some(temp) => temp};
-                let print_color = match piece_color {
-                    PieceColor::Red => Color::RED,
-                    PieceColor::Purple => Color::MAGENTA,
-                    PieceColor::Green => Color::GREEN,
-                    PieceColor::Blue => Color::BLUE,
-                    PieceColor::Cyan => Color::CYAN,
-                    PieceColor::Orange => Color::new(1.0,0.5, 0.2, 1.0),
-                    PieceColor::Yellow => Color::YELLOW,
-                    PieceColor::Black => Color::BLACK,
-                    PieceColor::Gray => Color::new(0.5,0.5, 0.5, 1.0)
+    pub fn draw_board(&self, ctx: &Context, canvas: &mut Canvas) {
+        let cell = self.cell_size(ctx);
+        let board = self.game.board();
+        for (row, y) in board.visible_rows().enumerate() {
+            // Rows waiting out the line clear delay flash white.
+            let flashing = self.clearing_rows.contains(&y) && (self.game.elapsed_ms() / 100).is_multiple_of(2);
+            for x in 0..board.width() {
+                let color = match board.get(x, y) {
+                    _ if flashing => Color::WHITE,
+                    Some(piece_color) => to_color(piece_color),
+                    None => to_color(PieceColor::Black)
                 };
-                let rect = graphics::Rect::new(((x as f32) * 30.0) + 100.0, (y as f32) * 30.0,30.0,30.0);
-                canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(rect.point()).scale(rect.size()).color(print_color));
-                x = x + 1;
+                let rect = graphics::Rect::new(((x as f32) * cell) + BOARD_LEFT, (row as f32) * cell, cell, cell);
+                canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(rect.point()).scale(rect.size()).color(color));
             }
-            y = y + 1;
         }
 
-        self.draw_piece(&mut canvas, self.current_piece.rotation[self.current_piece.rotation_state as usize], self.current_piece.x, self.get_drop_shadow_y(), PieceColor::Gray);
-        self.draw_piece(&mut canvas, self.current_piece.rotation[self.current_piece.rotation_state as usize], self.current_piece.x, self.current_piece.y, self.current_piece.piece_color);
-
-        canvas.draw(graphics::Text::new("NEXT:").set_scale(24.), glam::vec2(410.0, 0.0));
-
-        let next_box = graphics::Rect::new(410.0, 20.0, 120.0, 120.0);
-        canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(next_box.point()).scale(next_box.size()).color(Color::BLACK));
-        self.draw_next_box_and_hold_box(&mut canvas, self.next_piece.rotation[0], 410.0, 20.0, self.next_piece.piece_color);
-
-        canvas.draw(graphics::Text::new("HOLD:").set_scale(24.), glam::vec2(410.0, 150.0));
-
-        let next_box = graphics::Rect::new(410.0, 170.0, 120.0, 120.0);
-        canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(next_box.point()).scale(next_box.size()).color(Color::BLACK));
+        // Between pieces the last piece is already part of the board.
+        if self.game.is_piece_active() {
+            let current_piece = self.game.current_piece();
+            self.draw_piece(canvas, cell, current_piece.get_rotation_state(), current_piece.x, self.game.get_drop_shadow_y(), PieceColor::Gray);
+            self.draw_piece(canvas, cell, current_piece.get_rotation_state(), current_piece.x, current_piece.y, current_piece.piece_color);
+        }
 
-        if self.hold_piece.is_some() {
-            let hold_piece = match self.hold_piece { None => Piece::get_piece(), Some(temp) => temp };
-            self.draw_next_box_and_hold_box(&mut canvas, hold_piece.rotation[0], 410.0, 170.0, hold_piece.piece_color);
+        // The next queue runs down the right of the board, the first piece larger than the rest,
+        // with the hold box underneath it.
+        let side_left = BOARD_LEFT + cell * board.width() as f32 + 10.0;
+        let mut top = 0.0;
+        if !self.game.next_pieces().is_empty() {
+            canvas.draw(graphics::Text::new("NEXT:").set_scale(24.), glam::vec2(side_left, top));
+            top += 20.0;
+        }
+        for (index, next_piece) in self.game.next_pieces().iter().enumerate() {
+            let cell = if index == 0 { PREVIEW_CELL_SIZE } else { QUEUE_CELL_SIZE };
+            self.draw_next_box_and_hold_box(canvas, cell, next_piece.rotation[0], side_left, top, to_color(next_piece.piece_color));
+            top += 4.0 * cell + 10.0;
         }
-    }
 
-    /// Draws the a piece in the next box or the hold box based on the location given.
-    fn draw_next_box_and_hold_box(&self, canvas: &mut Canvas, rotation: u32, x: f32, y: f32, color: PieceColor) {
-        let mut iterations = 0;
-        let mut func = |x: f32, y: f32| {
-
-            let print_color = match color {
-                PieceColor::Red => Color::RED,
-                PieceColor::Purple => Color::MAGENTA,
-                PieceColor::Green => Color::GREEN,
-                PieceColor::Blue => Color::BLUE,
-                PieceColor::Cyan => Color::CYAN,
-                PieceColor::Orange => Color::new(1.0,0.5, 0.2, 100.0),
-                PieceColor::Yellow => Color::YELLOW,
-                PieceColor::Black => Color::BLACK,
-                PieceColor::Gray => Color::new(0.5,0.5, 0.5, 1.0)
-            };
-            let rect = graphics::Rect::new(x, y,30.0,30.0);
-            canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(rect.point()).scale(rect.size()).color(print_color));
-        };
-        while iterations < 16
-        {
-            if (rotation & (0x8000 >> iterations)) > 0 {
-                func(x + (((iterations % 4) as f32) * 30.0), y + (((iterations / 4) as f32) * 30.0));
-            }
-            iterations = iterations + 1;
+        // The hold box is dimmed while the current piece can't be swapped into it.
+        let shade = |color: Color| if self.game.can_hold() { color } else { dim(color) };
+        canvas.draw(graphics::Text::new("HOLD:").set_scale(24.), graphics::DrawParam::new().dest(glam::vec2(side_left, top)).color(shade(Color::WHITE)));
+        match self.game.hold_piece() {
+            Some(hold_piece) => self.draw_next_box_and_hold_box(canvas, PREVIEW_CELL_SIZE, hold_piece.rotation[0], side_left, top + 20.0, shade(to_color(hold_piece.piece_color))),
+            None => self.draw_next_box_and_hold_box(canvas, PREVIEW_CELL_SIZE, 0, side_left, top + 20.0, Color::BLACK)
         }
     }
 
-    /// Draws given piece to the board.
-    fn draw_piece(&self, canvas: &mut Canvas, rotation: u32, x: i8, y: i8, color: PieceColor) {
-        let mut iterations = 0;
-        let mut func = |x: i8, y: i8| {
-
-            let print_color = match color {
-                PieceColor::Red => Color::RED,
-                PieceColor::Purple => Color::MAGENTA,
-                PieceColor::Green => Color::GREEN,
-                PieceColor::Blue => Color::BLUE,
-                PieceColor::Cyan => Color::CYAN,
-                PieceColor::Orange => Color::new(1.0,0.5, 0.2, 100.0),
-                PieceColor::Yellow => Color::YELLOW,
-                PieceColor::Black => Color::BLACK,
-                PieceColor::Gray => Color::new(0.5,0.5, 0.5, 1.0)
-            };
-            let rect = graphics::Rect::new(((x as f32) * 30.0) + 100.0, (y as f32) * 30.0,30.0,30.0);
-            canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(rect.point()).scale(rect.size()).color(print_color));
-        };
-        while iterations < 16
-        {
-            if (rotation & (0x8000 >> iterations)) > 0 {
-                func(x + (iterations % 4), y + (iterations / 4));
+    /// Draws the high score table for the title screen.
+    fn draw_high_scores(&self, canvas: &mut Canvas) {
+        canvas.draw(graphics::Text::new(format!("HIGH SCORES: {}", self.game.mode().label())).set_scale(32.0), glam::vec2(100.0, 20.0));
+        let columns = [10.0, 50.0, 210.0, 310.0, 370.0, 440.0];
+        let header = ["#", "NAME", "SCORE", "LINES", "TIME", "DATE"];
+        for (x, text) in columns.iter().zip(header) {
+            canvas.draw(graphics::Text::new(text).set_scale(20.0), glam::vec2(*x, 80.0));
+        }
+        for (rank, entry) in self.high_scores.entries().iter().enumerate() {
+            let y = 110.0 + 30.0 * rank as f32;
+            let row = [
+                (rank + 1).to_string(),
+                entry.name.clone(),
+                entry.score.to_string(),
+                entry.lines.to_string(),
+                format_time(entry.duration_ms),
+                highscores::format_date(entry.date)
+            ];
+            for (x, text) in columns.iter().zip(row) {
+                canvas.draw(graphics::Text::new(text).set_scale(20.0), glam::vec2(*x, y));
             }
-            iterations = iterations + 1;
         }
+        if self.high_scores.entries().is_empty() {
+            canvas.draw(graphics::Text::new("No games yet").set_scale(24.0), glam::vec2(190.0, 120.0));
+        }
+        canvas.draw(graphics::Text::new("'Escape' to go back").set_scale(24.0), glam::vec2(165.0, 560.0));
     }
-}
-
-/// Piece struct.
-#[derive(Copy, Clone)]
-struct Piece {
-    /// Current Rotate this piece is in.
-    rotation_state: i8,
-    /// X coordinate of the piece with respect to the board.
-    x: i8,
-    /// Y coordinate of the piece with respect to the board.
-    y: i8,
-    /// Array of the possible rotations of the piece.
-    rotation: [u32; 4],
-    /// Piece color when represented on the board.
-    piece_color: PieceColor
-}
 
-/// Tetris piece implementation.
-impl Piece {
-    fn new(piece_color: PieceColor, rotation: [u32; 4]) -> Self {
-        Piece {
-            rotation_state: 0,
-            x: 4,
-            y: 0,
-            rotation,
-            piece_color
+    /// Draws a box of 4x4 cells `cell` pixels wide at the location given, with a piece in it
+    /// for the next queue or the hold box.
+    fn draw_next_box_and_hold_box(&self, canvas: &mut Canvas, cell: f32, rotation: u32, x: f32, y: f32, color: Color) {
+        let piece_box = graphics::Rect::new(x, y, 4.0 * cell, 4.0 * cell);
+        canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(piece_box.point()).scale(piece_box.size()).color(Color::BLACK));
+        for (dx, dy) in mask_cells(rotation) {
+            let rect = graphics::Rect::new(x + ((dx as f32) * cell), y + ((dy as f32) * cell), cell, cell);
+            canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(rect.point()).scale(rect.size()).color(color));
         }
     }
 
-    /// Helper function to grab the current rotation the piece is on.
-    fn get_rotation_state(&self) -> u32 { return self.rotation[self.rotation_state as usize]; }
-
-    /// static function that constructs and returns a random tetris piece.
-    pub fn get_piece() -> Piece {
-        match rand::thread_rng().gen_range(0..7)
-        {
-            0 => Piece::new(PieceColor::Cyan, [0x00F0, 0x2222, 0x00F0, 0x2222]),
-            1 => Piece::new(PieceColor::Blue, [0x44C0, 0x8E00, 0x6440, 0x0E20]),
-            2 => Piece::new(PieceColor::Orange, [0x4460, 0x0E80, 0xC440, 0x2E00]),
-            3 => Piece::new(PieceColor::Yellow, [0xCC00, 0xCC00, 0xCC00, 0xCC00]),
-            4 => Piece::new(PieceColor::Green, [0x06C0, 0x4620, 0x06C0, 0x4620]),
-            5 => Piece::new(PieceColor::Purple, [0x0E40, 0x4C40, 0x4E00, 0x4640]),
-            6 => Piece::new(PieceColor::Red, [0x0C60, 0x2640, 0x0C60, 0x2640]),
-            _ => Piece::new(PieceColor::Cyan, [0x00F0, 0x2222, 0x00F0, 0x2222])
+    /// Draws given piece to the board, with cells `cell` pixels wide.
+    /// Blocks in the hidden rows above the board are left out.
+    fn draw_piece(&self, canvas: &mut Canvas, cell: f32, rotation: u32, x: i8, y: i8, color: PieceColor) {
+        let top = self.game.board().hidden_rows() as i8;
+        for (dx, dy) in mask_cells(rotation).filter(|&(_, dy)| y + dy >= top) {
+            let rect = graphics::Rect::new((((x + dx) as f32) * cell) + BOARD_LEFT, ((y + dy - top) as f32) * cell, cell, cell);
+            canvas.draw(&graphics::Quad, graphics::DrawParam::new().dest(rect.point()).scale(rect.size()).color(to_color(color)));
         }
     }
 }
 
-/// Next we create an enum that will represent all the possible
-/// inputs our piece needs to handle
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum GameInput {
-    Down,
-    Left,
-    Right,
-    HardDrop,
-    RotateRight,
-    RotateLeft,
-    Hold,
-    Start
-}
-
-impl GameInput {
-    /// We also create a helper function that will let us convert between a
-    /// `ggez` `Keycode` and the `GameInput` that it represents. Of course,
-    /// not every keycode represents a direction, so we return `None` if this
-    /// is the case.
-    pub fn from_keycode(key: KeyCode) -> Option<GameInput> {
-        return match key {
-            KeyCode::D => Some(GameInput::Right),
-            KeyCode::A => Some(GameInput::Left),
-            KeyCode::S => Some(GameInput::Down),
-            KeyCode::W => Some(GameInput::HardDrop),
-            KeyCode::E => Some(GameInput::Hold),
-            KeyCode::J => Some(GameInput::RotateLeft),
-            KeyCode::K => Some(GameInput::RotateRight),
-            KeyCode::Space => Some(GameInput::Start),
-            _ => None,
-        }
+/// Color used to draw a block of the given piece color.
+fn to_color(piece_color: PieceColor) -> Color {
+    match piece_color {
+        PieceColor::Red => Color::RED,
+        PieceColor::Purple => Color::MAGENTA,
+        PieceColor::Green => Color::GREEN,
+        PieceColor::Blue => Color::BLUE,
+        PieceColor::Cyan => Color::CYAN,
+        PieceColor::Orange => Color::new(1.0, 0.5, 0.2, 1.0),
+        PieceColor::Yellow => Color::YELLOW,
+        PieceColor::Black => Color::BLACK,
+        PieceColor::Gray => Color::new(0.5, 0.5, 0.5, 1.0)
     }
 }
 
+/// A darker shade of the given color, for parts of the display that are unavailable.
+fn dim(color: Color) -> Color {
+    Color::new(color.r * 0.35, color.g * 0.35, color.b * 0.35, color.a)
+}
 
+/// `path` if nothing is there yet, otherwise the first free `<stem>-2.<extension>`, `<stem>-3.<extension>`
+/// and so on beside it, so every game of a session keeps its own recording.
+fn free_record_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    let numbered = (2..).map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)));
+    std::iter::once(path.to_path_buf()).chain(numbered).find(|candidate| !candidate.exists()).unwrap_or_else(|| path.to_path_buf())
+}
 
 // Then we implement the `ggez:event::EventHandler` trait on it, which
 // requires callbacks for updating and drawing the game state each frame.
 //
 // The `EventHandler` trait also contains callbacks for event handling
 // that you can override if you wish, but the defaults are fine.
-impl event::EventHandler<ggez::GameError> for GameState {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult {
-        if self.is_playing {
-            if self.global_timer.elapsed_ms() > (self.last_piece_dropped_time + (1000 - (5 * (self.lines_cleared_count as i64)))) {
-                self.move_down(false);
-                self.last_piece_dropped_time = self.global_timer.elapsed_ms();
-            }
-            if self.global_timer.elapsed_ms() > (self.last_fps_poll_time + 1000){
-                self.display_fps = self.fps_count;
-                self.fps_count = 0;
-                self.last_fps_poll_time = self.global_timer.elapsed_ms();
+impl event::EventHandler<ggez::GameError> for MainState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        // Nothing moves on the title screen, the menu, or while paused.
+        // The engine only advances by the frame time we hand it.
+        if self.game.is_playing() {
+            match &mut self.playback {
+                Some(player) => {
+                    player.step(&mut self.game, ctx.time.delta());
+                    if player.is_finished(&self.game) {
+                        self.check_playback();
+                        // A recording that stopped mid-game ended with the player leaving for the menu.
+                        if self.game.is_playing() {
+                            self.open_menu();
+                        }
+                    }
+                }
+                None => self.game.step(ctx.time.delta())
             }
-            self.fps_count = self.fps_count + 1;
         }
-
+        self.handle_events();
         Ok(())
     }
 
@@ -481,64 +458,187 @@ impl event::EventHandler<ggez::GameError> for GameState {
             graphics::Color::from([0.1, 0.2, 0.3, 1.0]),
         );
 
-        if self.is_playing {
-            self.draw_board(&mut canvas);
-
-            canvas.draw(graphics::Text::new("SCORE:").set_scale(24.), glam::vec2(0.0, 0.0));
-            canvas.draw(graphics::Text::new(self.score.to_string()).set_scale(24.), glam::vec2(0.0, 20.0));
-            canvas.draw(graphics::Text::new("LINES:").set_scale(24.), glam::vec2(0.0, 60.0));
-            canvas.draw(graphics::Text::new(self.lines_cleared_count.to_string()).set_scale(24.), glam::vec2(0.0, 80.0));
-            canvas.draw(graphics::Text::new("FPS:").set_scale(24.), glam::vec2(0.0, 120.0));
-            canvas.draw(graphics::Text::new(self.display_fps.to_string()).set_scale(24.), glam::vec2(0.0, 140.0));
+        let start_key = self.keys.keys_for(GameInput::Start).first().map_or("Start".to_string(), |key| format!("{:?}", key));
+        match self.game.play_state() {
+            PlayState::Playing | PlayState::Paused => {
+                // The board stays hidden while paused so a pause can't be used to plan ahead.
+                if self.game.is_playing() {
+                    self.draw_board(ctx, &mut canvas);
+                }
+                else {
+                    canvas.draw(graphics::Text::new("PAUSED").set_scale(40.0), glam::vec2(190.0, 150.0));
+                    canvas.draw(graphics::Text::new("'Escape' to resume").set_scale(24.0), glam::vec2(160.0, 210.0));
+                    canvas.draw(graphics::Text::new("'Q' to quit to the menu").set_scale(24.0), glam::vec2(140.0, 240.0));
+                }
 
-        }
-        else {
-            canvas.draw(graphics::Text::new("Press 'Space' to Start!").set_scale(40.0), glam::vec2(30.0,150.0));
+                let mut hud = self.hud_stats();
+                hud.push(("FPS:", format!("{:.0}", ctx.time.fps())));
+                for (row, (label, value)) in hud.iter().enumerate() {
+                    let y = 60.0 * row as f32;
+                    canvas.draw(graphics::Text::new(*label).set_scale(24.), glam::vec2(0.0, y));
+                    canvas.draw(graphics::Text::new(value.as_str()).set_scale(24.), glam::vec2(0.0, y + 20.0));
+                }
+            }
+            PlayState::Menu => {
+                canvas.draw(graphics::Text::new("MENU").set_scale(40.0), glam::vec2(210.0, 150.0));
+                for (index, item) in MenuItem::ALL.iter().enumerate() {
+                    let marker = if index == self.menu_selection { "> " } else { "  " };
+                    let text = match item {
+                        MenuItem::Mode => format!("{}{}: {}", marker, item.label(), self.game.mode().label()),
+                        _ => format!("{}{}", marker, item.label())
+                    };
+                    canvas.draw(graphics::Text::new(text).set_scale(32.0), glam::vec2(150.0, 220.0 + 40.0 * index as f32));
+                }
+            }
+            PlayState::GameOver => {
+                let heading = match self.game.mode() {
+                    GameMode::Ultra { .. } if self.game.completed() => "TIME UP!",
+                    _ if self.game.completed() => "FINISHED!",
+                    _ => "GAME OVER"
+                };
+                canvas.draw(graphics::Text::new(heading).set_scale(40.0), glam::vec2(180.0, 40.0));
+                let reason = match self.game.top_out() {
+                    Some(TopOut::BlockOut) => "Block out",
+                    Some(TopOut::LockOut) => "Lock out",
+                    Some(TopOut::GarbageOut) => "Pushed out by garbage",
+                    None => ""
+                };
+                canvas.draw(graphics::Text::new(reason).set_scale(24.0), glam::vec2(180.0, 80.0));
+                for (row, (label, value)) in self.final_stats().iter().enumerate() {
+                    let y = 110.0 + 36.0 * row as f32;
+                    canvas.draw(graphics::Text::new(*label).set_scale(28.0), glam::vec2(140.0, y));
+                    canvas.draw(graphics::Text::new(value.as_str()).set_scale(28.0), glam::vec2(320.0, y));
+                }
+                if let Some(name) = &self.name_entry {
+                    canvas.draw(graphics::Text::new("NEW HIGH SCORE!").set_scale(28.0), glam::vec2(170.0, 420.0));
+                    canvas.draw(graphics::Text::new(format!("Name: {}_", name)).set_scale(28.0), glam::vec2(140.0, 455.0));
+                    canvas.draw(graphics::Text::new("'Enter' to save, 'Escape' to skip").set_scale(20.0), glam::vec2(120.0, 495.0));
+                }
+                else {
+                    if self.last_rank == Some(0) && self.game.mode().ranks_by_time() {
+                        canvas.draw(graphics::Text::new("New personal best!").set_scale(28.0), glam::vec2(150.0, 410.0));
+                    }
+                    else if let Some(rank) = self.last_rank {
+                        canvas.draw(graphics::Text::new(format!("High score #{}!", rank + 1)).set_scale(28.0), glam::vec2(170.0, 410.0));
+                    }
+                    canvas.draw(graphics::Text::new(format!("'{}' to play again", start_key)).set_scale(24.0), glam::vec2(160.0, 450.0));
+                    canvas.draw(graphics::Text::new("'Escape' for the title screen").set_scale(24.0), glam::vec2(120.0, 480.0));
+                }
+            }
+            PlayState::Title if self.show_high_scores => self.draw_high_scores(&mut canvas),
+            PlayState::Title => {
+                canvas.draw(graphics::Text::new("TETRIS").set_scale(40.0), glam::vec2(210.0, 60.0));
+                canvas.draw(graphics::Text::new(self.game.mode().label()).set_scale(28.0), glam::vec2(210.0, 105.0));
+                canvas.draw(graphics::Text::new(format!("Press '{}' to Start!", start_key)).set_scale(40.0), glam::vec2(30.0,150.0));
+                canvas.draw(graphics::Text::new("'Escape' for the menu").set_scale(24.0), glam::vec2(150.0, 210.0));
+                canvas.draw(graphics::Text::new("'H' for high scores").set_scale(24.0), glam::vec2(165.0, 240.0));
+            }
         }
 
         canvas.finish(ctx)?;
         Ok(())
     }
 
-    fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
-        if self.is_playing {
-            if let Some(dir) = input.keycode.and_then(GameInput::from_keycode){
-                match dir {
-                    GameInput::Down => self.move_down(true),
-                    GameInput::Left => self.move_direction(dir),
-                    GameInput::Right => self.move_direction(dir),
-                    GameInput::RotateRight => self.rotate(dir),
-                    GameInput::RotateLeft => self.rotate(dir),
-                    GameInput::HardDrop => self.hard_drop(),
-                    GameInput::Hold => self.hold(),
-                    _ => false
-                };
-            }
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeat: bool) -> GameResult {
+        // Held keys are repeated by the engine's DAS/ARR, not the OS key repeat.
+        if repeat {
+            return Ok(());
         }
-        else {
+        let Some(key) = input.keycode else {
+            return Ok(());
+        };
+        let bound = self.keys.input_for(key);
 
-            if let Some(dir) = input.keycode.and_then(GameInput::from_keycode){
-               let will_start =  match dir {
-                    GameInput::Start => true,
-                    _ => false
-                };
-                if will_start {
-                    self.score = 0;
-                    self.global_timer.restart();
-                    self.lines_cleared_count = 0;
-                    self.board = [[None; 20]; 10];
-                    self.hold_piece = None;
-                    self.next_piece = Piece::get_piece();
-                    self.current_piece = Piece::get_piece();
-                    self.last_piece_dropped_time = self.global_timer.elapsed_ms();
-                    self.last_fps_poll_time = self.global_timer.elapsed_ms();
-                    self.fps_count = 0;
-                    self.is_playing = true;
+        match self.game.play_state() {
+            PlayState::Playing if key == KeyCode::Escape => {
+                self.game.pause();
+            }
+            PlayState::Playing => {
+                if let (Some(dir), None) = (bound, &self.playback) {
+                    if let Some(replay) = &mut self.recording {
+                        replay.record(self.game.elapsed_ms(), ReplayAction::Press(dir));
+                    }
+                    self.game.press(dir);
+                    // A hard drop or hold can end the game. Wrap it up now, before a start pressed
+                    // in the same frame replaces the game and its recording.
+                    if !self.game.is_playing() {
+                        self.handle_events();
+                    }
+                }
+            }
+            PlayState::Paused => match key {
+                KeyCode::Escape => {
+                    self.paused_by_focus = false;
+                    self.game.resume();
+                }
+                KeyCode::Q => self.open_menu(),
+                _ => {}
+            },
+            PlayState::Menu => self.menu_key(ctx, key),
+            PlayState::GameOver if self.name_entry.is_some() => self.name_entry_key(key),
+            PlayState::GameOver if key == KeyCode::Escape => self.game.return_to_title(),
+            PlayState::Title if self.show_high_scores => {
+                if key == KeyCode::Escape || key == KeyCode::H {
+                    self.show_high_scores = false;
+                }
+            }
+            PlayState::Title if key == KeyCode::H => self.show_high_scores = true,
+            PlayState::Title if key == KeyCode::Escape => self.open_menu(),
+            PlayState::Title | PlayState::GameOver => {
+                if bound == Some(GameInput::Start) {
+                    self.start_game();
                 }
             }
         }
         Ok(())
     }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if let Some(name) = &mut self.name_entry {
+            if !character.is_control() && name.chars().count() < highscores::MAX_NAME_LEN {
+                name.push(character);
+            }
+        }
+        Ok(())
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> GameResult {
+        if self.playback.is_some() {
+            return Ok(());
+        }
+        // Releases still reach a paused game so nothing is left held down once it resumes.
+        if let Some(dir) = input.keycode.and_then(|key| self.keys.input_for(key)) {
+            if let Some(replay) = &mut self.recording {
+                replay.record(self.game.elapsed_ms(), ReplayAction::Release(dir));
+            }
+            self.game.release(dir);
+        }
+        Ok(())
+    }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) -> GameResult {
+        if !gained {
+            self.paused_by_focus = self.game.pause() || self.paused_by_focus;
+        }
+        else if self.auto_resume && self.paused_by_focus {
+            self.paused_by_focus = false;
+            self.game.resume();
+        }
+        Ok(())
+    }
+
+    fn quit_event(&mut self, _ctx: &mut Context) -> Result<bool, ggez::GameError> {
+        // Keep the recording of a game that was still going when the window closed.
+        self.save_recording();
+        Ok(false)
+    }
+}
+
+/// Detach from the console window Windows opens alongside the game.
+#[cfg(all(windows, feature = "hide-console"))]
+fn hide_console() {
+    // FreeConsole has no preconditions; it just fails if there is no console to detach from.
+    unsafe { winapi::um::wincon::FreeConsole() };
 }
 
 // Now our main function, which does three things:
@@ -549,21 +649,82 @@ impl event::EventHandler<ggez::GameError> for GameState {
 // * Second, create a `ggez::game::Game` object which will
 // do the work of creating our MainState and running our game.
 // * Then, just call `game.run()` which runs the `Game` mainloop.
+//
+// `--seed <n>` replays the same piece sequence every game,
+// `--ruleset <guideline|classic|tgm>` picks a preset, `--width <n>` and `--height <n>` size the board
+// and `--hidden-rows <n>` sets how many rows are hidden above it. `--preview <0-6>` sets how many
+// upcoming pieces are shown, and `--hold <off|once|unlimited>` sets how often the hold can be used.
+// `--hold-reset <on|off>` sets whether held pieces go back to their spawn position.
+// `--initial-rotation <on|off>` and `--initial-hold <on|off>` let rotations and hold held down act
+// on pieces as they enter.
+// `--randomizer <uniform|7-bag|14-bag|history>` and `--rotation <srs|ars|classic>`
+// override how pieces are drawn and rotated, and `--lock-delay <ms>` and
+// `--lock-mode <infinity|move|step|gravity>` override how long pieces rest before locking.
+// `--entry-delay <ms>` and `--line-clear-delay <ms>` set how long the next piece waits to enter
+// and how long completed lines stay on the board.
+// `--das <ms>`, `--arr <ms>` and `--sdf <factor>` tune held movement keys.
+// `--scoring <guideline|classic>` picks how points are awarded, and `--level <n>` (1 to 99)
+// and `--gravity <guideline|classic|20g>` set the starting level and how fast pieces fall.
+// `--record <file>` saves a replay of every game, numbering the files after the first, and `--replay <file>` plays one back.
+// `--mode <marathon|sprint|ultra|dig>` picks what to play for, `--sprint-lines <n>` sets the sprint
+// target and `--ultra-seconds <n>` the ultra time limit. `--dig-rows <n>`, `--messiness <percent>`
+// and `--endless <on|off>` set how much garbage a dig starts with, how often its hole moves and
+// whether cleared garbage is replaced.
+// `--auto-resume <on|off>` resumes a game paused by losing focus once the window gets it back.
 pub fn main() -> GameResult {
-    // Hide the Console...
-    unsafe { winapi::um::wincon::FreeConsole() };
+    let mut ruleset = Ruleset::default();
+    let mut seed = None;
+    let mut record_path = None;
+    let mut playback = None;
+    let mut auto_resume = false;
+    let mut mode = GameMode::Marathon;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--seed", Some(value)) => seed = value.parse().ok(),
+            ("--record", Some(value)) => record_path = Some(PathBuf::from(value)),
+            ("--replay", Some(value)) => match Replay::load(&value) {
+                Ok(replay) => playback = Some(replay),
+                Err(error) => eprintln!("Could not load replay {}: {}", value, error)
+            },
+            ("--auto-resume", Some(value)) => auto_resume = value == "on",
+            (flag, Some(value)) => {
+                let known = ruleset.apply_arg(flag, &value) || mode.apply_arg(flag, &value);
+                if !known {
+                    eprintln!("Ignoring unknown option {}.", flag);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let ruleset = ruleset.normalized();
+
+    // Replays are watched in the mode they were recorded in.
+    if let Some(replay) = &playback {
+        mode = replay.mode;
+    }
+
+    eprintln!("Starting Tetris with {} randomizer, {} rotation, {} scoring and {} gravity from level {}.",
+        ruleset.randomizer.name(), ruleset.rotation_system.name(), ruleset.scoring.name(), ruleset.gravity.name(), ruleset.start_level);
+    #[cfg(all(windows, feature = "hide-console"))]
+    hide_console();
     // We add the CARGO_MANIFEST_DIR/resources to the resource paths
     // so that ggez will look in our cargo project directory for files.
     let (ctx, events_loop) = ggez::ContextBuilder::new("Tetris", "Payton Trosclair")
         // Next we set up the window. This title will be displayed in the title bar of the window.
         .window_setup(ggez::conf::WindowSetup::default().title("Tetris!"))
-        // Now we get to set the size of the window, which we use our SCREEN_SIZE constant from earlier to help with
-        .window_mode(ggez::conf::WindowMode::default().dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1))
-        // And finally we attempt to build the context and create the window. If it fails, we panic with the message
-        // "Failed to build ggez context"
-        .build()?;
-
-
-    let state = GameState::new();
+        // Now we get to set the size of the window, which we use our SCREEN_SIZE constant from earlier to help with.
+        // The board is scaled to whatever size the window is resized to.
+        .window_mode(ggez::conf::WindowMode::default().dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1).resizable(true))
+        // And finally we attempt to build the context and create the window. If it fails, we report why and give up.
+        .build()
+        .map_err(|error| {
+            eprintln!("Could not open the game window: {}", error);
+            error
+        })?;
+
+
+    let state = MainState::new(ruleset, mode, seed, record_path, playback, KeyBindings::load_or_default(), auto_resume);
     event::run(ctx, events_loop, state)
 }
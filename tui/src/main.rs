@@ -0,0 +1,397 @@
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use tetris_engine::{format_time, mask_cells, new_seed, GameEvent, GameInput, GameMode, GameState, PieceColor, PlayState, Ruleset, TopOut};
+
+/// Column the score, lines, level, hold box and final stats are drawn at.
+const STATS_LEFT: u16 = 0;
+/// Column of the board's left border. Every cell is two characters wide.
+const BOARD_LEFT: u16 = 12;
+/// Longest we wait for input before simulating and drawing another frame.
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+/// Puts the terminal into raw mode on the alternate screen and restores it when dropped,
+/// so the shell is usable again however the game exits.
+struct TerminalGuard {
+    /// Did the terminal accept the request to report key releases.
+    reports_key_releases: bool
+}
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+        // Only terminals that speak the kitty keyboard protocol report key releases.
+        let reports_key_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_key_releases {
+            execute!(io::stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+        Ok(TerminalGuard { reports_key_releases })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.reports_key_releases {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(io::stdout(), ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Terminal frontend state wrapping the headless engine.
+struct TuiState {
+    /// Engine holding the board, pieces and score.
+    game: GameState,
+    /// Seed every game is started from, or `None` to pick a fresh one each game.
+    seed: Option<u64>,
+    /// Does the terminal report key releases. Without them every key press is a single tap.
+    reports_key_releases: bool,
+    /// Completed rows flashing until they collapse, counted like the board's rows.
+    clearing_rows: Vec<usize>
+}
+
+impl TuiState {
+    /// Constructor for the TuiState struct.
+    fn new(ruleset: Ruleset, mode: GameMode, seed: Option<u64>, reports_key_releases: bool) -> Self {
+        TuiState {
+            game: GameState::with_mode(ruleset, mode, seed.unwrap_or_else(new_seed)),
+            seed,
+            reports_key_releases,
+            clearing_rows: Vec::new()
+        }
+    }
+
+    /// Advance the game by `dt` and keep track of the rows it is about to clear.
+    fn step(&mut self, dt: Duration) {
+        self.game.step(dt);
+        for event in self.game.drain_events() {
+            match event {
+                GameEvent::LinesMarked { rows } => self.clearing_rows = rows,
+                GameEvent::LinesCollapsed { .. } => self.clearing_rows.clear(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Handle a key event. Returns `false` once the player asked to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let is_ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+        if is_ctrl_c || key.code == KeyCode::Esc || key.code == KeyCode::Char('q') {
+            return false;
+        }
+        if key.kind == KeyEventKind::Press && key.code == KeyCode::Char('p') {
+            if !self.game.pause() {
+                self.game.resume();
+            }
+            return true;
+        }
+        let Some(input) = input_from_keycode(key.code) else {
+            return true;
+        };
+
+        match key.kind {
+            KeyEventKind::Press if self.game.is_playing() => {
+                self.game.press(input);
+                if !self.reports_key_releases {
+                    self.game.release(input);
+                }
+            }
+            KeyEventKind::Press if input == GameInput::Start && self.game.play_state() != PlayState::Paused => {
+                self.clearing_rows.clear();
+                self.game.start(self.seed.unwrap_or_else(new_seed));
+            }
+            KeyEventKind::Release => self.game.release(input),
+            // Held keys are repeated by the engine's DAS/ARR, not the terminal.
+            _ => {}
+        }
+        true
+    }
+
+    /// Column the next queue is drawn at, just right of the board.
+    fn side_left(&self) -> u16 {
+        BOARD_LEFT + 2 * self.game.board().width() as u16 + 4
+    }
+
+    /// Draws the whole frame: stats, board, current piece, drop shadow, the next queue and the hold box.
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        self.draw_stats(out)?;
+        self.draw_board(out)?;
+
+        // The next queue runs down the right of the board, with every piece after the first
+        // drawn at half width. Like the board, the next queue and the hold box are blanked
+        // while paused so a pause can't be used to plan ahead.
+        let paused = self.game.play_state() == PlayState::Paused;
+        let side_left = self.side_left();
+        if !self.game.next_pieces().is_empty() {
+            queue!(out, MoveTo(side_left, 1), SetForegroundColor(Color::White), Print("NEXT:"))?;
+        }
+        for (index, next_piece) in self.game.next_pieces().iter().enumerate() {
+            let rotation = if paused { 0 } else { next_piece.rotation[0] };
+            match index {
+                0 => draw_box(out, side_left, 2, rotation, to_color(next_piece.piece_color))?,
+                _ => draw_small_box(out, side_left, 4 + 3 * index as u16, rotation, to_color(next_piece.piece_color))?
+            }
+        }
+
+        // The hold box is greyed out while holding isn't allowed.
+        let shade = |color: Color| if self.game.can_hold() { color } else { Color::DarkGrey };
+        queue!(out, MoveTo(STATS_LEFT, 13), SetForegroundColor(shade(Color::White)), Print("HOLD:"))?;
+        match self.game.hold_piece().filter(|_| !paused) {
+            Some(hold_piece) => draw_box(out, STATS_LEFT, 14, hold_piece.rotation[0], shade(to_color(hold_piece.piece_color)))?,
+            None => draw_box(out, STATS_LEFT, 14, 0, Color::Black)?
+        }
+        self.draw_final_stats(out)?;
+
+        queue!(out, ResetColor)?;
+        out.flush()
+    }
+
+    /// Draws the score, lines, level and time to the left of the board.
+    /// Sprint counts lines against its target, ultra counts the clock down and dig shows
+    /// the garbage left, or cleared in an endless dig, in place of the lines.
+    fn draw_stats(&self, out: &mut impl Write) -> io::Result<()> {
+        let lines = match self.game.mode() {
+            GameMode::Sprint { lines: target } => ("LINES:", format!("{}/{}", self.game.lines_cleared_count(), target)),
+            GameMode::Dig { endless: false, .. } => ("GARBAGE:", self.game.board().garbage_lines().to_string()),
+            GameMode::Dig { endless: true, .. } => ("DUG:", self.game.stats().garbage_cleared.to_string()),
+            _ => ("LINES:", self.game.lines_cleared_count().to_string())
+        };
+        let time = match self.game.mode().time_limit_ms() {
+            Some(limit_ms) => format_time(limit_ms.saturating_sub(self.game.elapsed_ms())),
+            None => format_time(self.game.elapsed_ms())
+        };
+        let stats = [
+            ("SCORE:", self.game.score().to_string()),
+            lines,
+            ("LEVEL:", self.game.level().to_string()),
+            ("TIME:", time)
+        ];
+        for (row, (label, value)) in stats.iter().enumerate() {
+            let y = 1 + 3 * row as u16;
+            queue!(out, SetForegroundColor(Color::White), MoveTo(STATS_LEFT, y), Print(label), MoveTo(STATS_LEFT, y + 1), Print(format!("{:<10}", value)))?;
+        }
+        Ok(())
+    }
+
+    /// Draws the rest of the final stats under the hold box once the game is over,
+    /// and blanks them out otherwise. Labels are kept short to fit left of the board.
+    fn draw_final_stats(&self, out: &mut impl Write) -> io::Result<()> {
+        let stats = self.game.stats();
+        let elapsed_ms = self.game.elapsed_ms();
+        let lines = [
+            format!("PIECES {}", stats.pieces_placed),
+            format!("PPS    {:.2}", stats.pieces_per_second(elapsed_ms)),
+            format!("TETRIS {}", stats.tetrises),
+            format!("T-SPIN {}", stats.t_spins)
+        ];
+        let is_over = self.game.play_state() == PlayState::GameOver;
+        queue!(out, SetForegroundColor(Color::White))?;
+        for (row, line) in lines.iter().enumerate() {
+            let text = if is_over { line.as_str() } else { "" };
+            queue!(out, MoveTo(STATS_LEFT, 19 + row as u16), Print(format!("{:<11}", text)))?;
+        }
+        Ok(())
+    }
+
+    /// Draws the visible rows of the board with its border, the current piece and its drop shadow.
+    /// While no game is running a message is shown on top instead of the pieces,
+    /// and a paused game hides the board entirely.
+    fn draw_board(&self, out: &mut impl Write) -> io::Result<()> {
+        let board = self.game.board();
+        let top = board.hidden_rows() as i8;
+        let mut cells = vec![vec![None; board.height()]; board.width()];
+        if self.game.play_state() != PlayState::Paused {
+            for (x, column) in cells.iter_mut().enumerate() {
+                for (cell, y) in column.iter_mut().zip(board.visible_rows()) {
+                    // Rows waiting out the line clear delay flash white.
+                    let flashing = self.clearing_rows.contains(&y) && (self.game.elapsed_ms() / 100).is_multiple_of(2);
+                    *cell = board.get(x, y).map(|color| (if flashing { Color::White } else { to_color(color) }, "██"));
+                }
+            }
+        }
+        // Between pieces the last piece is already part of the board.
+        if self.game.is_playing() && self.game.is_piece_active() {
+            let mut shadow = *self.game.current_piece();
+            shadow.y = self.game.get_drop_shadow_y();
+            // Blocks still in the hidden rows above the board aren't drawn.
+            for (x, y) in shadow.blocks().filter(|&(_, y)| y >= top) {
+                cells[x as usize][(y - top) as usize] = Some((to_color(PieceColor::Gray), "[]"));
+            }
+            let current_piece = self.game.current_piece();
+            for (x, y) in current_piece.blocks().filter(|&(_, y)| y >= top) {
+                cells[x as usize][(y - top) as usize] = Some((to_color(current_piece.piece_color), "██"));
+            }
+        }
+
+        let border = format!("+{}+", "-".repeat(2 * board.width()));
+        queue!(out, SetForegroundColor(Color::White), MoveTo(BOARD_LEFT, 0), Print(&border))?;
+        for y in 0..board.height() {
+            let row = y as u16 + 1;
+            queue!(out, SetForegroundColor(Color::White), MoveTo(BOARD_LEFT, row), Print("|"))?;
+            for column in &cells {
+                match column[y] {
+                    Some((color, text)) => queue!(out, SetForegroundColor(color), Print(text))?,
+                    None => queue!(out, SetForegroundColor(Color::DarkGrey), Print(" ."))?
+                }
+            }
+            queue!(out, SetForegroundColor(Color::White), Print("|"))?;
+        }
+        queue!(out, SetForegroundColor(Color::White), MoveTo(BOARD_LEFT, board.height() as u16 + 1), Print(&border))?;
+
+        if !self.game.is_playing() {
+            let (title, prompt) = match self.game.play_state() {
+                PlayState::Paused => ("PAUSED", "P to resume"),
+                PlayState::GameOver if self.game.completed() && self.game.mode().time_limit_ms().is_some() => ("TIME UP!", "Space to start"),
+                PlayState::GameOver if self.game.completed() => ("FINISHED!", "Space to start"),
+                PlayState::GameOver if self.game.top_out() == Some(TopOut::LockOut) => ("LOCK OUT", "Space to start"),
+                PlayState::GameOver if self.game.top_out() == Some(TopOut::BlockOut) => ("BLOCK OUT", "Space to start"),
+                PlayState::GameOver => ("GAME OVER", "Space to start"),
+                _ => ("TETRIS", "Space to start")
+            };
+            // Centred over the board, spilling past its borders on boards too narrow for the text.
+            let width = (2 * board.width()).max(16);
+            let left = (BOARD_LEFT + 1 + board.width() as u16).saturating_sub(width as u16 / 2);
+            let row = board.height() as u16 / 2;
+            queue!(out, MoveTo(left, row), Print(format!("{:^width$}", title)))?;
+            queue!(out, MoveTo(left, row + 1), Print(format!("{:^width$}", prompt)))?;
+            queue!(out, MoveTo(left, row + 2), Print(format!("{:^width$}", "Q to quit")))?;
+        }
+        Ok(())
+    }
+}
+
+/// Draws a half width preview of the given rotation mask at the given position, two rows
+/// tall starting from the mask's first filled row. Every piece in its spawn orientation fits.
+fn draw_small_box(out: &mut impl Write, left: u16, top: u16, rotation: u32, color: Color) -> io::Result<()> {
+    let first_row = mask_cells(rotation).map(|(_, dy)| dy).min().unwrap_or(0);
+    let mut rows = [[false; 4]; 2];
+    for (dx, dy) in mask_cells(rotation).filter(|&(_, dy)| dy - first_row < 2) {
+        rows[(dy - first_row) as usize][dx as usize] = true;
+    }
+    for (dy, row) in rows.iter().enumerate() {
+        queue!(out, MoveTo(left, top + dy as u16))?;
+        for &filled in row {
+            if filled {
+                queue!(out, SetForegroundColor(color), Print("█"))?;
+            }
+            else {
+                queue!(out, Print(" "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Draws a 4x4 preview box with the given rotation mask at the given position.
+fn draw_box(out: &mut impl Write, left: u16, top: u16, rotation: u32, color: Color) -> io::Result<()> {
+    let mut rows = [[false; 4]; 4];
+    for (dx, dy) in mask_cells(rotation) {
+        rows[dy as usize][dx as usize] = true;
+    }
+    for (dy, row) in rows.iter().enumerate() {
+        queue!(out, MoveTo(left, top + dy as u16))?;
+        for &filled in row {
+            if filled {
+                queue!(out, SetForegroundColor(color), Print("██"))?;
+            }
+            else {
+                queue!(out, Print("  "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Terminal color used to draw a block of the given piece color.
+fn to_color(piece_color: PieceColor) -> Color {
+    match piece_color {
+        PieceColor::Red => Color::Red,
+        PieceColor::Purple => Color::Magenta,
+        PieceColor::Green => Color::Green,
+        PieceColor::Blue => Color::Blue,
+        PieceColor::Cyan => Color::Cyan,
+        PieceColor::Orange => Color::DarkYellow,
+        PieceColor::Yellow => Color::Yellow,
+        PieceColor::Black => Color::Black,
+        PieceColor::Gray => Color::Grey
+    }
+}
+
+/// Convert a terminal key to the `GameInput` it represents, using the window frontend's
+/// default bindings. Shift is left out, since terminals only report it with other keys.
+fn input_from_keycode(key: KeyCode) -> Option<GameInput> {
+    match key {
+        KeyCode::Right => Some(GameInput::Right),
+        KeyCode::Left => Some(GameInput::Left),
+        KeyCode::Down => Some(GameInput::Down),
+        KeyCode::Up => Some(GameInput::HardDrop),
+        KeyCode::Enter => Some(GameInput::Start),
+        KeyCode::Char(c) => match c.to_ascii_lowercase() {
+            'd' => Some(GameInput::Right),
+            'a' => Some(GameInput::Left),
+            's' => Some(GameInput::Down),
+            'w' => Some(GameInput::HardDrop),
+            'c' | 'e' => Some(GameInput::Hold),
+            'z' | 'j' => Some(GameInput::RotateLeft),
+            'x' | 'k' => Some(GameInput::RotateRight),
+            ' ' => Some(GameInput::Start),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// Simulate, draw and read keys until the player quits.
+fn run(state: &mut TuiState) -> io::Result<()> {
+    let mut out = BufWriter::new(io::stdout());
+    let mut last_step = Instant::now();
+    loop {
+        if event::poll(FRAME_TIME)? {
+            match event::read()? {
+                Event::Key(key) if !state.handle_key(key) => return Ok(()),
+                Event::Resize(_, _) => queue!(out, Clear(ClearType::All))?,
+                _ => {}
+            }
+        }
+
+        // The engine only advances by the time we hand it.
+        let now = Instant::now();
+        state.step(now - last_step);
+        last_step = now;
+
+        state.draw(&mut out)?;
+    }
+}
+
+// `--seed <n>` replays the same piece sequence every game. Every other flag picks the rules or the
+// mode the same way it does for the window frontend, through `Ruleset::apply_arg` and `GameMode::apply_arg`.
+fn main() -> io::Result<()> {
+    let mut ruleset = Ruleset::default();
+    let mut seed = None;
+    let mut mode = GameMode::Marathon;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--seed", Some(value)) => seed = value.parse().ok(),
+            (flag, Some(value)) => {
+                let known = ruleset.apply_arg(flag, &value) || mode.apply_arg(flag, &value);
+                if !known {
+                    eprintln!("Ignoring unknown option {}.", flag);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let ruleset = ruleset.normalized();
+    let guard = TerminalGuard::enter()?;
+    let mut state = TuiState::new(ruleset, mode, seed, guard.reports_key_releases);
+    run(&mut state)
+}